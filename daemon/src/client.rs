@@ -0,0 +1,275 @@
+use bincode::config::{self, Config, Configuration};
+use std::{future::Future, io, time::Duration};
+use tokio::time::timeout;
+
+use interprocess::local_socket::tokio::{RecvHalf, SendHalf, Stream, prelude::*};
+
+use crate::{
+    CreateTopicRequest, DeleteIdeaRequest, ErrorCode, HealthResponse, IdeaEntry, InfoResponse,
+    ListTopicRequest, Request, Response, SearchTopicRequest, StatusResponse, UpdateTopicRequest,
+    daemon::DaemonSocket,
+    protocol::{self, IDEA_IDS_VERSION, INFO_CHUNKING_VERSION, ProtocolError},
+};
+
+pub const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(2);
+/// Requests embed their input, which may take a while on slow providers.
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Debug, thiserror::Error)]
+pub enum ClientError {
+    #[error("Daemon is not reachable: {0}")]
+    Connect(io::Error),
+    #[error("Daemon did not answer within {0:?}")]
+    Timeout(Duration),
+    #[error("Protocol error: {0}")]
+    Protocol(#[from] ProtocolError),
+    #[error("Daemon closed the connection")]
+    Disconnected,
+    /// The daemon hung up during the handshake, which it does to users it doesn't allow.
+    #[error("Daemon refused the connection; this user may not be allowed to use it")]
+    Rejected,
+    #[error("Daemon failed the request ({code}): {message}")]
+    Remote { code: ErrorCode, message: String },
+    #[error("Unexpected response from the daemon: {0}")]
+    UnexpectedResponse(String),
+    /// The running daemon predates the request; restarting it picks up the new binary.
+    #[error(
+        "Daemon speaks protocol version {negotiated}, this request needs {needed}; restart the daemon"
+    )]
+    Outdated { needed: u16, negotiated: u16 },
+}
+
+type Result<T> = std::result::Result<T, ClientError>;
+
+struct Connection {
+    reader: RecvHalf,
+    writer: SendHalf,
+    /// Protocol version negotiated during the handshake
+    version: u16,
+}
+
+/// Client for the daemon listening on a [`DaemonSocket`].
+///
+/// Requests are sent one at a time over a single connection. A request that
+/// times out drops the connection, since its response may still arrive, and
+/// the next request reconnects.
+pub struct DaemonClient<C: Config = Configuration> {
+    connection: Option<Connection>,
+    socket: DaemonSocket,
+    config: C,
+    connect_timeout: Duration,
+    request_timeout: Duration,
+}
+
+impl DaemonClient {
+    /// Connect to the namespaced socket using the standard bincode configuration.
+    pub async fn connect() -> Result<Self> {
+        Self::connect_to(DaemonSocket::default()).await
+    }
+
+    pub async fn connect_to(socket: DaemonSocket) -> Result<Self> {
+        Self::connect_with_config(socket, config::standard()).await
+    }
+}
+
+impl<C: Config> DaemonClient<C> {
+    pub async fn connect_with_config(socket: DaemonSocket, config: C) -> Result<Self> {
+        let mut client = Self {
+            connection: None,
+            socket,
+            config,
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+            request_timeout: DEFAULT_REQUEST_TIMEOUT,
+        };
+        client.connection().await?;
+        Ok(client)
+    }
+
+    pub fn with_connect_timeout(mut self, connect_timeout: Duration) -> Self {
+        self.connect_timeout = connect_timeout;
+        self
+    }
+
+    pub fn with_request_timeout(mut self, request_timeout: Duration) -> Self {
+        self.request_timeout = request_timeout;
+        self
+    }
+
+    /// Store an idea and return its id.
+    pub async fn create_idea(&mut self, topic_name: &str, content: &str) -> Result<u64> {
+        let request = Request::CreateTopic(CreateTopicRequest {
+            topic_name: topic_name.to_string(),
+            content: content.to_string(),
+        });
+        match self.request_since(IDEA_IDS_VERSION, &request).await? {
+            Response::CreateTopic(response) => Ok(response.idea_id),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    pub async fn update_idea(&mut self, topic_name: &str, content: &str) -> Result<u64> {
+        let request = Request::UpdateTopic(UpdateTopicRequest {
+            topic_name: topic_name.to_string(),
+            content: content.to_string(),
+        });
+        match self.request_since(IDEA_IDS_VERSION, &request).await? {
+            Response::UpdateTopic(response) => Ok(response.idea_id),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    pub async fn search(
+        &mut self,
+        topic_name: Option<&str>,
+        query: &str,
+        limit: u64,
+    ) -> Result<Vec<IdeaEntry>> {
+        let request = Request::SearchTopic(SearchTopicRequest {
+            topic_name: topic_name.map(str::to_string),
+            query: query.to_string(),
+            limit,
+        });
+        match self.request_since(IDEA_IDS_VERSION, &request).await? {
+            Response::SearchTopic(response) => Ok(response.ideas),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    pub async fn list(&mut self, topic_name: &str, limit: u32) -> Result<Vec<IdeaEntry>> {
+        let request = Request::ListTopic(ListTopicRequest {
+            topic_name: topic_name.to_string(),
+            limit,
+        });
+        match self.request_since(IDEA_IDS_VERSION, &request).await? {
+            Response::ListTopic(response) => Ok(response.ideas),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    pub async fn delete(&mut self, idea_id: u64) -> Result<()> {
+        let request = Request::DeleteIdea(DeleteIdeaRequest { idea_id });
+        match self.request(&request).await? {
+            Response::DeleteIdea(_) => Ok(()),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    pub async fn status(&mut self) -> Result<StatusResponse> {
+        match self.request(&Request::Status).await? {
+            Response::Status(response) => Ok(response),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    pub async fn ping(&mut self) -> Result<()> {
+        match self.request(&Request::Ping).await? {
+            Response::Pong => Ok(()),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    /// Check that the daemon can reach Qdrant and its embedding provider.
+    pub async fn health(&mut self) -> Result<HealthResponse> {
+        match self.request(&Request::Health).await? {
+            Response::Health(response) => Ok(response),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    pub async fn info(&mut self) -> Result<InfoResponse> {
+        match self
+            .request_since(INFO_CHUNKING_VERSION, &Request::Info)
+            .await?
+        {
+            Response::Info(response) => Ok(response),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    /// Ask the daemon to stop accepting connections and exit.
+    pub async fn shutdown(&mut self) -> Result<()> {
+        match self.request(&Request::Shutdown).await? {
+            Response::Shutdown => Ok(()),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    /// Send a request and wait for its response, turning error responses into errors.
+    pub async fn request(&mut self, request: &Request) -> Result<Response> {
+        self.request_since(request.since_version(), request).await
+    }
+
+    /// Send a request whose response is only understood from protocol
+    /// `version` on, refusing to when the daemon is older.
+    async fn request_since(&mut self, version: u16, request: &Request) -> Result<Response> {
+        let config = self.config;
+        let request_timeout = self.request_timeout;
+        let connection = self.connection().await?;
+        let needed = version.max(request.since_version());
+        if connection.version < needed {
+            return Err(ClientError::Outdated {
+                needed,
+                negotiated: connection.version,
+            });
+        }
+        let exchange = async {
+            protocol::send(&mut connection.writer, request, config).await?;
+            protocol::recv(&mut connection.reader, config)
+                .await?
+                .ok_or(ClientError::Disconnected)
+        };
+        let response = match with_timeout(request_timeout, exchange).await {
+            Ok(response) => response,
+            Err(e) => {
+                self.connection = None;
+                return Err(e);
+            }
+        };
+        match response {
+            Response::Error { code, message } => Err(ClientError::Remote { code, message }),
+            response => Ok(response),
+        }
+    }
+
+    async fn connection(&mut self) -> Result<&mut Connection> {
+        if self.connection.is_none() {
+            let connect = async {
+                let name = self.socket.name().map_err(ClientError::Connect)?;
+                let (mut reader, mut writer) = Stream::connect(name)
+                    .await
+                    .map_err(ClientError::Connect)?
+                    .split();
+                let version = protocol::client_handshake(&mut reader, &mut writer)
+                    .await
+                    .map_err(|e| match e {
+                        ProtocolError::Io(e)
+                            if matches!(
+                                e.kind(),
+                                io::ErrorKind::UnexpectedEof | io::ErrorKind::ConnectionReset
+                            ) =>
+                        {
+                            ClientError::Rejected
+                        }
+                        e => e.into(),
+                    })?;
+                Ok(Connection {
+                    reader,
+                    writer,
+                    version,
+                })
+            };
+            self.connection = Some(with_timeout(self.connect_timeout, connect).await?);
+        }
+        Ok(self.connection.as_mut().unwrap())
+    }
+}
+
+async fn with_timeout<T>(duration: Duration, future: impl Future<Output = Result<T>>) -> Result<T> {
+    timeout(duration, future)
+        .await
+        .map_err(|_| ClientError::Timeout(duration))?
+}
+
+fn unexpected(response: Response) -> ClientError {
+    ClientError::UnexpectedResponse(format!("{:?}", response))
+}
@@ -1,112 +1,632 @@
 use bincode::config::Config;
 use eyre::{Result, WrapErr};
-use std::fs::File;
+use std::{
+    fs,
+    future::Future,
+    os::{fd::OwnedFd, unix::net::UnixStream},
+    path::PathBuf,
+    sync::{
+        Arc, Mutex, RwLock,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
 
 use daemonize::Daemonize;
 use embedding::EmbeddingModel;
-use storage_client::TopicStorage;
+use storage_client::{Idea, TopicStorage, TopicStorageError};
+use tracing::{Instrument, error, info, info_span, warn};
 
-use std::io::{self, BufReader, prelude::*};
+use std::io;
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    signal::unix::{SignalKind, signal},
+    sync::{Semaphore, watch},
+    time::{interval, sleep, timeout},
+};
 
-use interprocess::local_socket::{GenericNamespaced, ListenerOptions, prelude::*};
+use interprocess::{
+    local_socket::{
+        GenericFilePath, GenericNamespaced, ListenerOptions, Name,
+        tokio::{Listener, RecvHalf, Stream, prelude::*},
+    },
+    os::unix::local_socket::ListenerOptionsExt,
+};
+use nix::{
+    sys::socket::{UnixCredentials, getsockopt, sockopt::PeerCredentials},
+    unistd::Uid,
+};
 
 use crate::{
-    CreateTopicResponse, ListTopicResponse, Request, Response, SearchTopicResponse,
-    UpdateTopicResponse,
+    CreateTopicResponse, DeleteIdeaResponse, ErrorCode, HealthResponse, IdeaEntry, InfoResponse,
+    ListTopicResponse, Request, Response, SearchTopicResponse, StatusResponse, UpdateTopicResponse,
+    http::HttpGateway, metrics, paths::DaemonPaths, protocol, systemd,
 };
 
+pub const DEFAULT_MAX_CONNECTIONS: usize = 64;
+/// How long in-flight requests get to finish once the daemon shuts down.
+pub const DEFAULT_DRAIN_TIMEOUT: Duration = Duration::from_secs(10);
+/// Pause after a failed accept, so a lack of file descriptors doesn't spin.
+const ACCEPT_RETRY_DELAY: Duration = Duration::from_millis(100);
+
 pub struct Daemon<T: EmbeddingModel, C: Config> {
-    storage: TopicStorage<T>,
+    /// Swapped as a whole on reload; requests in flight keep the storage they started with.
+    storage: RwLock<Arc<TopicStorage<T>>>,
     config: C,
+    max_connections: usize,
+    drain_timeout: Duration,
+    socket: DaemonSocket,
+    /// Users allowed to connect besides the daemon's own
+    allowed_uids: Vec<u32>,
+    /// Listening socket inherited from systemd, used instead of binding one.
+    activation_socket: Mutex<Option<OwnedFd>>,
+    http_gateway: Option<HttpGateway>,
+    started: Instant,
+    requests: AtomicU64,
+    failed_requests: AtomicU64,
+    /// Flipped to `true` once; connections and the listener watch it.
+    shutdown: watch::Sender<bool>,
     //TODO: Add persistent storage for topics
 }
 
 pub const PRINT_NAME: &str = "qdrant-cli-daemon.sock";
 
-impl<T: EmbeddingModel, C: Config> Daemon<T, C> {
+/// Socket the daemon listens on and clients connect to.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum DaemonSocket {
+    /// [`PRINT_NAME`] in the abstract namespace, reachable by every local user
+    #[default]
+    Namespaced,
+    /// A socket file with mode 0600, reachable only by its owner
+    Path(PathBuf),
+}
+
+impl DaemonSocket {
+    pub fn name(&self) -> io::Result<Name<'_>> {
+        match self {
+            DaemonSocket::Namespaced => PRINT_NAME.to_ns_name::<GenericNamespaced>(),
+            DaemonSocket::Path(path) => path.as_path().to_fs_name::<GenericFilePath>(),
+        }
+    }
+}
+
+impl std::fmt::Display for DaemonSocket {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DaemonSocket::Namespaced => write!(f, "@{PRINT_NAME}"),
+            DaemonSocket::Path(path) => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+impl<T: EmbeddingModel + 'static, C: Config + Send + Sync + 'static> Daemon<T, C> {
     pub fn new(storage: TopicStorage<T>, config: C) -> Self {
-        Self { storage, config }
+        Self {
+            storage: RwLock::new(Arc::new(storage)),
+            config,
+            max_connections: DEFAULT_MAX_CONNECTIONS,
+            drain_timeout: DEFAULT_DRAIN_TIMEOUT,
+            socket: DaemonSocket::default(),
+            allowed_uids: Vec::new(),
+            activation_socket: Mutex::new(None),
+            http_gateway: None,
+            started: Instant::now(),
+            requests: AtomicU64::new(0),
+            failed_requests: AtomicU64::new(0),
+            shutdown: watch::Sender::new(false),
+        }
+    }
+
+    /// Maximum number of connections served at once; further clients wait
+    /// until a connection closes.
+    pub fn with_max_connections(mut self, max_connections: usize) -> Self {
+        self.max_connections = max_connections.max(1);
+        self
+    }
+
+    /// How long [`Daemon::listen`] waits for in-flight requests after a
+    /// shutdown before closing their connections.
+    pub fn with_drain_timeout(mut self, drain_timeout: Duration) -> Self {
+        self.drain_timeout = drain_timeout;
+        self
+    }
+
+    pub fn with_socket(mut self, socket: DaemonSocket) -> Self {
+        self.socket = socket;
+        self
+    }
+
+    /// Also accept connections from these users; by default only processes
+    /// running as the daemon's own user may connect.
+    pub fn with_allowed_uids(mut self, uids: impl IntoIterator<Item = u32>) -> Self {
+        self.allowed_uids.extend(uids);
+        self
+    }
+
+    /// Accept connections on an already listening socket, such as one passed
+    /// by systemd socket activation, instead of binding the configured one.
+    pub fn with_activation_socket(self, socket: Option<OwnedFd>) -> Self {
+        *self.activation_socket.lock().unwrap() = socket;
+        self
+    }
+
+    /// Also serve requests over HTTP, see [`HttpGateway`].
+    pub fn with_http_gateway(mut self, gateway: Option<HttpGateway>) -> Self {
+        self.http_gateway = gateway;
+        self
+    }
+
+    pub fn update_storage(&self, storage: TopicStorage<T>) {
+        *self.storage.write().unwrap() = Arc::new(storage);
+    }
+
+    fn storage(&self) -> Arc<TopicStorage<T>> {
+        Arc::clone(&self.storage.read().unwrap())
     }
-    pub fn update_storage(&mut self, storage: TopicStorage<T>) {
-        self.storage = storage;
+
+    /// Make [`Daemon::listen`] stop accepting connections, drain the ones in
+    /// flight and return.
+    pub fn shutdown(&self) {
+        self.shutdown.send_replace(true);
+    }
+
+    /// Serve connections until a `Shutdown` request, SIGTERM or SIGINT.
+    ///
+    /// SIGHUP calls `reload` and swaps in the storage it returns, keeping the
+    /// listener and open connections. A failed reload keeps the old storage.
+    /// When systemd enabled its watchdog, it is pinged while serving.
+    pub async fn serve<F, Fut>(self: Arc<Self>, reload: F) -> Result<()>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<TopicStorage<T>>>,
+    {
+        let mut terminate = signal(SignalKind::terminate())?;
+        let mut interrupt = signal(SignalKind::interrupt())?;
+        let mut hangup = signal(SignalKind::hangup())?;
+        let watchdog_interval = systemd::watchdog_interval();
+        // Only polled when the watchdog is enabled, so the fallback period is never used.
+        let mut watchdog = interval(watchdog_interval.unwrap_or(Duration::from_secs(60)));
+        let signals = async {
+            loop {
+                tokio::select! {
+                    _ = terminate.recv() => {
+                        info!("Received SIGTERM, shutting down");
+                        self.shutdown();
+                    }
+                    _ = interrupt.recv() => {
+                        info!("Received SIGINT, shutting down");
+                        self.shutdown();
+                    }
+                    _ = hangup.recv() => {
+                        systemd::notify_reloading();
+                        match reload().instrument(info_span!("reload")).await {
+                            Ok(storage) => {
+                                info!(
+                                    provider = %storage.model_info(),
+                                    collection = storage.collection_name(),
+                                    "Reloaded configuration"
+                                );
+                                self.update_storage(storage);
+                            }
+                            Err(e) => error!("Reload failed, keeping the old configuration: {e:#}"),
+                        }
+                        systemd::notify_ready();
+                    }
+                    _ = watchdog.tick(), if watchdog_interval.is_some() => systemd::notify_watchdog(),
+                }
+            }
+        };
+        let gateway = match &self.http_gateway {
+            Some(gateway) => {
+                let mut shutdown = self.shutdown.subscribe();
+                let stopped = async move {
+                    let _ = shutdown.wait_for(|stopped| *stopped).await;
+                };
+                Some(gateway.spawn(Arc::clone(&self), stopped).await?)
+            }
+            None => None,
+        };
+        let result = tokio::select! {
+            result = Arc::clone(&self).listen() => result,
+            never = signals => never,
+        };
+        if let Some(gateway) = gateway {
+            match timeout(self.drain_timeout, gateway).await {
+                Ok(Ok(Err(e))) => warn!("HTTP gateway failed: {e}"),
+                Ok(Err(e)) => warn!("HTTP gateway panicked: {e}"),
+                Ok(Ok(Ok(()))) => {}
+                Err(_) => warn!(
+                    "Closing HTTP requests still busy after {:?}",
+                    self.drain_timeout
+                ),
+            }
+        }
+        result
     }
 
     /// Listen for incoming connections on the daemon socket
     ///
+    /// Every connection is served by its own task, so a slow embedding call
+    /// only delays the client that made it.
+    ///
     /// # Warning
     ///
-    /// This function runs in an infinite loop listening for connections and will
-    /// not release the thread until an error occurs or the process is terminated.
-    /// Consider running this in a dedicated thread or using proper async runtime
-    /// management to avoid blocking the main thread.
-    pub async fn listen(&self) -> Result<()> {
-        let name = PRINT_NAME
-            .to_ns_name::<GenericNamespaced>()
-            .wrap_err("Failed to create namespaced name")?;
-        let opts = ListenerOptions::new().name(name);
-        let listener = match opts.create_sync() {
-            Err(e) if e.kind() == io::ErrorKind::AddrInUse => {
-                eprintln!(
-                    "Error: could not start server because the socket file is occupied. Please check if {PRINT_NAME} is in use by another process and try again."
-                );
-                return Err(e.into());
-            }
-            x => x?,
+    /// This function runs in a loop accepting connections and only returns
+    /// when the listener fails or the daemon is shut down. On shutdown the
+    /// socket is released right away, so a new daemon can start while the
+    /// requests in flight are drained.
+    pub async fn listen(self: Arc<Self>) -> Result<()> {
+        let activation_socket = self.activation_socket.lock().unwrap().take();
+        let listener = match activation_socket {
+            Some(socket) => Listener::from(
+                interprocess::os::unix::uds_local_socket::tokio::Listener::try_from(socket)
+                    .wrap_err("Failed to use the activation socket")?,
+            ),
+            None => self.socket.bind()?,
         };
+        systemd::notify_ready();
+        info!(socket = %self.socket, "Listening");
+
+        let connections = Arc::new(Semaphore::new(self.max_connections));
+        let mut shutdown = self.shutdown.subscribe();
+        loop {
+            let accepted = async {
+                let permit = Arc::clone(&connections)
+                    .acquire_owned()
+                    .await
+                    .wrap_err("Connection limiter closed")?;
+                Ok::<_, eyre::Error>((permit, listener.accept().await))
+            };
+            let (permit, conn) = tokio::select! {
+                accepted = accepted => accepted?,
+                _ = shutdown.wait_for(|stopped| *stopped) => break,
+            };
+            // Running out of file descriptors or a client hanging up before it
+            // is accepted doesn't stop the daemon.
+            let conn: Stream = match conn {
+                Ok(conn) => conn,
+                Err(e) => {
+                    warn!("Failed to accept a connection: {e}");
+                    drop(permit);
+                    sleep(ACCEPT_RETRY_DELAY).await;
+                    continue;
+                }
+            };
+            let daemon = Arc::clone(&self);
+            tokio::spawn(async move {
+                let (reader, writer) = conn.split();
+                let peer = match daemon.authorize(&reader) {
+                    Ok(peer) => peer,
+                    Err(e) => {
+                        error!("Rejected connection: {e:#}");
+                        return;
+                    }
+                };
+                let span = info_span!("connection", uid = peer.uid(), pid = peer.pid());
+                async {
+                    if let Err(e) = daemon.serve_connection(reader, writer).await {
+                        warn!("Connection failed: {e:#}");
+                    }
+                }
+                .instrument(span)
+                .await;
+                drop(permit);
+            });
+        }
+
+        systemd::notify_stopping();
+        drop(listener);
+        let in_flight = self.max_connections - connections.available_permits();
+        if in_flight > 0 {
+            info!("Waiting for {in_flight} connection(s) to finish");
+        }
+        let drained = timeout(
+            self.drain_timeout,
+            connections.acquire_many(self.max_connections as u32),
+        )
+        .await;
+        if drained.is_err() {
+            warn!(
+                "Closing connections still busy after {:?}",
+                self.drain_timeout
+            );
+        }
+        Ok(())
+    }
 
-        let mut buf: Vec<u8> = Vec::with_capacity(std::mem::size_of::<Response>());
+    /// Check the peer's credentials (`SO_PEERCRED`) against the allowed users.
+    fn authorize(&self, reader: &RecvHalf) -> Result<UnixCredentials> {
+        let RecvHalf::UdSocket(reader) = reader;
+        let peer =
+            getsockopt(reader, PeerCredentials).wrap_err("Failed to read the peer credentials")?;
+        let uid = peer.uid();
+        if uid == Uid::effective().as_raw() || self.allowed_uids.contains(&uid) {
+            Ok(peer)
+        } else {
+            Err(eyre::eyre!(
+                "uid {} (pid {}) is not allowed",
+                uid,
+                peer.pid()
+            ))
+        }
+    }
 
-        for conn in listener.incoming() {
-            let mut stream = BufReader::new(conn?);
-            let request: Request = bincode::decode_from_reader(&mut stream, self.config)?;
-            let response = self.process_request(request).await?;
-            buf.clear();
-            bincode::encode_into_slice(&response, &mut buf, self.config)?;
-            stream.get_mut().write_all(&buf)?;
+    /// Answer requests from one client until it disconnects.
+    async fn serve_connection(
+        &self,
+        mut reader: impl AsyncRead + Unpin,
+        mut writer: impl AsyncWrite + Unpin,
+    ) -> Result<()> {
+        let version = protocol::server_handshake(&mut reader, &mut writer).await?;
+        let mut shutdown = self.shutdown.subscribe();
+        loop {
+            // Idle connections are closed on shutdown; a request already read is answered.
+            let frame = tokio::select! {
+                frame = protocol::read_frame(&mut reader) => frame?,
+                _ = shutdown.wait_for(|stopped| *stopped) => break,
+            };
+            let Some(frame) = frame else { break };
+            // Frames keep the stream in sync, so a bad request only fails itself.
+            let response = match bincode::decode_from_slice::<Request, _>(&frame, self.config) {
+                // The client would not understand a response to a request it can't know about.
+                Ok((request, _)) if request.since_version() > version => {
+                    warn!(
+                        "Refused {} request on a version {version} connection",
+                        request.kind()
+                    );
+                    Response::error(
+                        ErrorCode::InvalidRequest,
+                        format!(
+                            "{} requests need protocol version {}, the connection negotiated {}",
+                            request.kind(),
+                            request.since_version(),
+                            version
+                        ),
+                    )
+                }
+                Ok((request, _)) => self.handle(request).await,
+                Err(e) => {
+                    warn!("Malformed request: {e}");
+                    Response::error(ErrorCode::InvalidRequest, format!("Malformed request: {e}"))
+                }
+            };
+            protocol::send(&mut writer, &response, self.config).await?;
         }
         Ok(())
     }
-    async fn process_request(&self, request: Request) -> Result<Response> {
+
+    /// Answer one request, whichever way it arrived.
+    pub(crate) async fn handle(&self, request: Request) -> Response {
+        // Request ids count requests since the daemon started.
+        let id = self.requests.fetch_add(1, Ordering::Relaxed) + 1;
+        let kind = request.kind();
+        let span = info_span!("request", id, kind);
+        let started = Instant::now();
+        let result = self.process_request(request).instrument(span.clone()).await;
+        metrics::record_request(kind, started.elapsed(), result.is_ok());
+        result.unwrap_or_else(|e| {
+            self.failed_requests.fetch_add(1, Ordering::Relaxed);
+            span.in_scope(|| error!("Request failed: {e}"));
+            Response::from(e)
+        })
+    }
+
+    async fn process_request(&self, request: Request) -> Result<Response, TopicStorageError> {
+        let storage = self.storage();
         match request {
             Request::CreateTopic(request) => {
-                self.storage
-                    .create_topic(&request.topic_name, &request.content)
-                    .await?;
-                Ok(Response::CreateTopic(CreateTopicResponse { success: true }))
+                let idea = Idea::new(request.topic_name.clone(), request.content, None);
+                let idea_id = storage.create_topic(&request.topic_name, idea).await?;
+                Ok(Response::CreateTopic(CreateTopicResponse {
+                    success: true,
+                    idea_id,
+                }))
             }
             Request::UpdateTopic(request) => {
-                self.storage
-                    .update_topic(&request.topic_name, &request.content)
-                    .await?;
-                Ok(Response::UpdateTopic(UpdateTopicResponse { success: true }))
+                let idea = Idea::new(request.topic_name.clone(), request.content, None);
+                let idea_id = storage.update_topic(&request.topic_name, idea).await?;
+                Ok(Response::UpdateTopic(UpdateTopicResponse {
+                    success: true,
+                    idea_id,
+                }))
             }
             Request::SearchTopic(request) => {
-                let results = self
-                    .storage
+                let ideas: Vec<IdeaEntry> = storage
                     .search_topic(request.topic_name.as_deref(), &request.query, request.limit)
-                    .await?;
-                Ok(Response::SearchTopic(SearchTopicResponse { results }))
+                    .await?
+                    .into_iter()
+                    .filter_map(IdeaEntry::from_stored)
+                    .collect();
+                Ok(Response::SearchTopic(SearchTopicResponse {
+                    results: ideas.iter().map(|idea| idea.content.clone()).collect(),
+                    ideas,
+                }))
             }
             Request::ListTopic(request) => {
-                let results = self
-                    .storage
+                let ideas: Vec<IdeaEntry> = storage
                     .list_topic(&request.topic_name, request.limit)
-                    .await?;
-                Ok(Response::ListTopic(ListTopicResponse { results }))
+                    .await?
+                    .into_iter()
+                    .filter_map(IdeaEntry::from_stored)
+                    .collect();
+                Ok(Response::ListTopic(ListTopicResponse {
+                    results: ideas.iter().map(|idea| idea.content.clone()).collect(),
+                    ideas,
+                }))
+            }
+            Request::DeleteIdea(request) => {
+                storage.delete_idea(request.idea_id).await?;
+                Ok(Response::DeleteIdea(DeleteIdeaResponse { success: true }))
+            }
+            Request::Status => Ok(Response::Status(StatusResponse {
+                pid: std::process::id(),
+                uptime_secs: self.started.elapsed().as_secs(),
+                provider: storage.model_info().to_string(),
+                collection: storage.collection_name().to_string(),
+                requests: self.requests.load(Ordering::Relaxed),
+                failed_requests: self.failed_requests.load(Ordering::Relaxed),
+            })),
+            Request::Shutdown => {
+                self.shutdown();
+                Ok(Response::Shutdown)
+            }
+            Request::Ping => Ok(Response::Pong),
+            Request::Health => {
+                let (qdrant, embedding) =
+                    tokio::join!(storage.check_qdrant(), storage.check_embedding());
+                Ok(Response::Health(HealthResponse {
+                    qdrant: qdrant.map_err(|e| e.to_string()),
+                    embedding: embedding.map_err(|e| e.to_string()),
+                }))
+            }
+            Request::Info => {
+                let points = storage.point_count().await.unwrap_or_else(|e| {
+                    warn!("Could not count points: {e}");
+                    None
+                });
+                Ok(Response::Info(InfoResponse {
+                    protocol_version: protocol::PROTOCOL_VERSION,
+                    min_protocol_version: protocol::MIN_PROTOCOL_VERSION,
+                    provider: storage.model_info().id.clone(),
+                    dimension: storage.model_info().dimension as u64,
+                    collection: storage.collection_name().to_string(),
+                    points,
+                    chunk_words: storage.chunking().map(|chunking| chunking.max_words as u64),
+                    chunk_overlap: storage
+                        .chunking()
+                        .map_or(0, |chunking| chunking.overlap_words as u64),
+                }))
             }
         }
     }
 }
 
-fn run_daemon<T: EmbeddingModel, C: Config>(daemon: Daemon<T, C>) -> eyre::Result<Daemonize<()>> {
-    let stdout = File::create("/tmp/qdrant-cli-daemon.log")?;
-    let stderr = File::create("/tmp/qdrant-cli-daemon.log")?;
-    let daemonize = Daemonize::new()
-        .pid_file("/tmp/qdrant-cli-daemon.pid")
+impl DaemonSocket {
+    fn bind(&self) -> Result<Listener> {
+        let name = self.name().wrap_err("Invalid socket name")?;
+        let opts = ListenerOptions::new().name(name).mode(0o600);
+        match opts.create_tokio() {
+            // A socket file nobody listens on is left over from a crashed daemon.
+            Err(e) if e.kind() == io::ErrorKind::AddrInUse && self.is_stale() => {
+                let DaemonSocket::Path(path) = self else {
+                    unreachable!()
+                };
+                warn!("Removing stale socket {}", path.display());
+                fs::remove_file(path)?;
+                self.bind()
+            }
+            Err(e) if e.kind() == io::ErrorKind::AddrInUse => {
+                error!(
+                    "Could not start server because the socket file is occupied. Please check if {self} is in use by another process and try again."
+                );
+                Err(e.into())
+            }
+            x => Ok(x?),
+        }
+    }
+
+    fn is_stale(&self) -> bool {
+        match self {
+            DaemonSocket::Namespaced => false,
+            DaemonSocket::Path(path) => UnixStream::connect(path)
+                .is_err_and(|e| e.kind() == io::ErrorKind::ConnectionRefused),
+        }
+    }
+}
+
+/// Detach from the terminal and run `serve` on a new tokio runtime in the
+/// daemon process, locking the pid file.
+///
+/// The calling process exits once the daemon is forked, so this has to be
+/// called before any runtime or other thread is started. The daemon has no
+/// terminal, so the error `serve` fails with is logged, and the pid file is
+/// removed once it returns.
+pub fn run_daemon<F: Future<Output = Result<()>>>(
+    paths: &DaemonPaths,
+    serve: impl FnOnce() -> F,
+) -> Result<()> {
+    Daemonize::new()
+        .pid_file(&paths.pid_file)
         .chown_pid_file(true)
-        .working_directory("/tmp")
-        .stdout(stdout)
-        .stderr(stderr);
+        // Keep relative paths in the configuration, such as key files, working.
+        .working_directory(std::env::current_dir()?)
+        .start()
+        .wrap_err("Failed to daemonize")?;
+
+    let result = run_foreground(serve);
+    if let Err(e) = &result {
+        error!("Daemon failed: {e:?}");
+    }
+    if let Err(e) = fs::remove_file(&paths.pid_file) {
+        warn!("Could not remove {}: {e}", paths.pid_file.display());
+    }
+    result
+}
+
+/// Run `serve` on a new tokio runtime without detaching, e.g. under systemd,
+/// which tracks the process and collects its output itself.
+pub fn run_foreground<F: Future<Output = Result<()>>>(serve: impl FnOnce() -> F) -> Result<()> {
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()?
+        .block_on(serve())
+}
 
-    Ok(daemonize)
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::{MAGIC, PROTOCOL_VERSION};
+    use embedding::hashing::HashingEmbedder;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    /// Connect to `daemon` as a client speaking only `version`.
+    async fn connect(
+        daemon: &Arc<Daemon<HashingEmbedder, bincode::config::Configuration>>,
+        version: u16,
+    ) -> tokio::io::DuplexStream {
+        let (mut client, server) = tokio::io::duplex(1024);
+        let daemon = Arc::clone(daemon);
+        tokio::spawn(async move {
+            let (reader, writer) = tokio::io::split(server);
+            daemon.serve_connection(reader, writer).await
+        });
+
+        client.write_all(&MAGIC).await.unwrap();
+        client.write_u16(version).await.unwrap();
+        client.write_u16(version).await.unwrap();
+        let mut header = [0; 8];
+        client.read_exact(&mut header).await.unwrap();
+        assert_eq!(client.read_u16().await.unwrap(), version);
+        client
+    }
+
+    async fn exchange(client: &mut tokio::io::DuplexStream, request: &Request) -> Response {
+        let config = bincode::config::standard();
+        protocol::send(client, request, config).await.unwrap();
+        protocol::recv(client, config).await.unwrap().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_refuses_requests_newer_than_the_connection() {
+        // Neither request reaches Qdrant, which is only contacted on use.
+        let storage = TopicStorage::new("http://localhost:6334", HashingEmbedder::new(8))
+            .await
+            .unwrap();
+        let daemon = Arc::new(Daemon::new(storage, bincode::config::standard()));
+
+        let mut v1 = connect(&daemon, 1).await;
+        match exchange(&mut v1, &Request::Ping).await {
+            Response::Error { code, message } => {
+                assert_eq!(code, ErrorCode::InvalidRequest);
+                assert!(message.contains("version 4"), "{message}");
+            }
+            response => panic!("Unexpected response {:?}", response),
+        }
+
+        let mut current = connect(&daemon, PROTOCOL_VERSION).await;
+        assert!(matches!(
+            exchange(&mut current, &Request::Ping).await,
+            Response::Pong
+        ));
+    }
 }
@@ -0,0 +1,507 @@
+//! REST gateway exposing the idea requests as HTTP/JSON endpoints, for
+//! clients that can't speak the socket protocol, such as scripts and phone
+//! shortcuts.
+//!
+//! Requests go through [`Daemon::handle`] like those read from the socket, so
+//! they share its request ids, logs and metrics.
+
+use axum::{
+    Json, Router,
+    extract::{Path, Query, Request as HttpRequest, State},
+    http::{StatusCode, header},
+    middleware::{self, Next},
+    response::{IntoResponse, Response as HttpResponse},
+    routing::{delete, get, post},
+};
+use bincode::config::Config;
+use embedding::EmbeddingModel;
+use eyre::{Result, WrapErr};
+use serde::{Deserialize, Serialize};
+use std::{fmt, future::Future, io, net::SocketAddr, sync::Arc};
+use tokio::{net::TcpListener, task::JoinHandle};
+use tracing::info;
+use utoipa::{
+    IntoParams, Modify, OpenApi, ToSchema,
+    openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme},
+};
+
+use crate::{
+    CreateTopicRequest, DeleteIdeaRequest, ErrorCode, IdeaEntry, ListTopicRequest, Request,
+    Response, SearchTopicRequest, daemon::Daemon,
+};
+
+/// Number of ideas returned when a request sets no `limit`, as in the CLI.
+const DEFAULT_LIMIT: u32 = 10;
+
+/// Address of the REST gateway and the token its clients must present.
+#[derive(Clone)]
+pub struct HttpGateway {
+    addr: SocketAddr,
+    token: Arc<str>,
+}
+
+impl HttpGateway {
+    /// Every endpoint but the OpenAPI document requires the header
+    /// `Authorization: Bearer <token>`.
+    pub fn new(addr: SocketAddr, token: impl Into<Arc<str>>) -> Self {
+        Self {
+            addr,
+            token: token.into(),
+        }
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Bind the gateway's address and serve `daemon` from a new task until
+    /// `shutdown` completes, after which requests in flight are finished.
+    pub(crate) async fn spawn<T, C>(
+        &self,
+        daemon: Arc<Daemon<T, C>>,
+        shutdown: impl Future<Output = ()> + Send + 'static,
+    ) -> Result<JoinHandle<io::Result<()>>>
+    where
+        T: EmbeddingModel + 'static,
+        C: Config + Send + Sync + 'static,
+    {
+        let listener = TcpListener::bind(self.addr)
+            .await
+            .wrap_err_with(|| format!("Failed to listen on {}", self.addr))?;
+        info!(addr = %self.addr, "Serving HTTP");
+        let router = router(daemon, Arc::clone(&self.token));
+        Ok(tokio::spawn(async move {
+            axum::serve(listener, router)
+                .with_graceful_shutdown(shutdown)
+                .await
+        }))
+    }
+}
+
+impl fmt::Debug for HttpGateway {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HttpGateway")
+            .field("addr", &self.addr)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Description of the endpoints, served at `/openapi.json`.
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "qdrant-cli",
+        description = "Store ideas and search them by meaning"
+    ),
+    paths(create_idea, search_ideas, list_topic, delete_idea),
+    components(schemas(NewIdea, CreatedIdea, Idea, Ideas, ErrorBody)),
+    modifiers(&BearerAuth),
+    security(("bearer" = []))
+)]
+pub struct ApiDoc;
+
+struct BearerAuth;
+
+impl Modify for BearerAuth {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        openapi
+            .components
+            .get_or_insert_default()
+            .add_security_scheme(
+                "bearer",
+                SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()),
+            );
+    }
+}
+
+/// An idea to store
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct NewIdea {
+    /// Topic the idea belongs to
+    pub topic: String,
+    pub content: String,
+}
+
+/// Id given to a stored idea
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct CreatedIdea {
+    /// Id to delete the idea with
+    pub id: u64,
+}
+
+/// A stored idea
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct Idea {
+    /// Id to delete the idea with
+    pub id: u64,
+    /// Topic the idea belongs to
+    pub topic: String,
+    pub content: String,
+    /// Chunk of a long idea that matched the search, if it was split
+    pub snippet: Option<String>,
+}
+
+impl From<IdeaEntry> for Idea {
+    fn from(entry: IdeaEntry) -> Self {
+        Self {
+            id: entry.id,
+            topic: entry.topic_name,
+            content: entry.content,
+            snippet: entry.snippet,
+        }
+    }
+}
+
+/// Ideas matching a request, best match first for searches
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct Ideas {
+    pub results: Vec<Idea>,
+}
+
+impl Ideas {
+    fn new(entries: Vec<IdeaEntry>) -> Self {
+        Self {
+            results: entries.into_iter().map(Idea::from).collect(),
+        }
+    }
+}
+
+/// Why a request failed
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ErrorBody {
+    /// Broad class of the failure, such as "storage error"
+    pub code: String,
+    pub message: String,
+}
+
+impl ErrorBody {
+    fn new(code: impl fmt::Display, message: impl Into<String>) -> Self {
+        Self {
+            code: code.to_string(),
+            message: message.into(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct SearchParams {
+    /// Text to search for
+    q: String,
+    /// Only search ideas of this topic
+    topic: Option<String>,
+    /// Maximum number of ideas to return (10 by default)
+    limit: Option<u64>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct ListParams {
+    /// Maximum number of ideas to return (10 by default)
+    limit: Option<u32>,
+}
+
+fn router<T, C>(daemon: Arc<Daemon<T, C>>, token: Arc<str>) -> Router
+where
+    T: EmbeddingModel + 'static,
+    C: Config + Send + Sync + 'static,
+{
+    Router::new()
+        .route("/ideas", post(create_idea::<T, C>))
+        .route("/ideas/search", get(search_ideas::<T, C>))
+        .route("/ideas/{id}", delete(delete_idea::<T, C>))
+        .route("/topics/{name}/ideas", get(list_topic::<T, C>))
+        .route_layer(middleware::from_fn_with_state(token, require_token))
+        .with_state(daemon)
+        .route("/openapi.json", get(|| async { Json(ApiDoc::openapi()) }))
+}
+
+async fn require_token(
+    State(token): State<Arc<str>>,
+    request: HttpRequest,
+    next: Next,
+) -> HttpResponse {
+    let authorized = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|given| constant_time_eq(given.as_bytes(), token.as_bytes()));
+    if authorized {
+        next.run(request).await
+    } else {
+        (
+            StatusCode::UNAUTHORIZED,
+            [(header::WWW_AUTHENTICATE, "Bearer")],
+            Json(ErrorBody::new(
+                "unauthorized",
+                "Missing or wrong bearer token",
+            )),
+        )
+            .into_response()
+    }
+}
+
+/// Compare without stopping at the first difference, so the response time
+/// doesn't tell how much of a guessed token was right.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+#[utoipa::path(
+    post,
+    path = "/ideas",
+    request_body = NewIdea,
+    responses(
+        (status = 201, description = "The idea was stored", body = CreatedIdea),
+        (status = "4XX", body = ErrorBody),
+        (status = "5XX", body = ErrorBody),
+    )
+)]
+async fn create_idea<T, C>(
+    State(daemon): State<Arc<Daemon<T, C>>>,
+    Json(idea): Json<NewIdea>,
+) -> HttpResponse
+where
+    T: EmbeddingModel + 'static,
+    C: Config + Send + Sync + 'static,
+{
+    let request = Request::CreateTopic(CreateTopicRequest {
+        topic_name: idea.topic,
+        content: idea.content,
+    });
+    reply(daemon.handle(request).await)
+}
+
+#[utoipa::path(
+    get,
+    path = "/ideas/search",
+    params(SearchParams),
+    responses(
+        (status = 200, body = Ideas),
+        (status = "4XX", body = ErrorBody),
+        (status = "5XX", body = ErrorBody),
+    )
+)]
+async fn search_ideas<T, C>(
+    State(daemon): State<Arc<Daemon<T, C>>>,
+    Query(params): Query<SearchParams>,
+) -> HttpResponse
+where
+    T: EmbeddingModel + 'static,
+    C: Config + Send + Sync + 'static,
+{
+    let request = Request::SearchTopic(SearchTopicRequest {
+        topic_name: params.topic,
+        query: params.q,
+        limit: params.limit.unwrap_or(DEFAULT_LIMIT.into()),
+    });
+    reply(daemon.handle(request).await)
+}
+
+#[utoipa::path(
+    get,
+    path = "/topics/{name}/ideas",
+    params(("name" = String, Path, description = "Topic to list"), ListParams),
+    responses(
+        (status = 200, body = Ideas),
+        (status = "4XX", body = ErrorBody),
+        (status = "5XX", body = ErrorBody),
+    )
+)]
+async fn list_topic<T, C>(
+    State(daemon): State<Arc<Daemon<T, C>>>,
+    Path(name): Path<String>,
+    Query(params): Query<ListParams>,
+) -> HttpResponse
+where
+    T: EmbeddingModel + 'static,
+    C: Config + Send + Sync + 'static,
+{
+    let request = Request::ListTopic(ListTopicRequest {
+        topic_name: name,
+        limit: params.limit.unwrap_or(DEFAULT_LIMIT),
+    });
+    reply(daemon.handle(request).await)
+}
+
+#[utoipa::path(
+    delete,
+    path = "/ideas/{id}",
+    params(("id" = u64, Path, description = "Id of the idea")),
+    responses(
+        (status = 204, description = "The idea and its chunks were deleted"),
+        (status = "4XX", body = ErrorBody),
+        (status = "5XX", body = ErrorBody),
+    )
+)]
+async fn delete_idea<T, C>(
+    State(daemon): State<Arc<Daemon<T, C>>>,
+    Path(id): Path<u64>,
+) -> HttpResponse
+where
+    T: EmbeddingModel + 'static,
+    C: Config + Send + Sync + 'static,
+{
+    let request = Request::DeleteIdea(DeleteIdeaRequest { idea_id: id });
+    reply(daemon.handle(request).await)
+}
+
+fn reply(response: Response) -> HttpResponse {
+    match response {
+        Response::CreateTopic(response) => (
+            StatusCode::CREATED,
+            Json(CreatedIdea {
+                id: response.idea_id,
+            }),
+        )
+            .into_response(),
+        Response::DeleteIdea(_) => StatusCode::NO_CONTENT.into_response(),
+        Response::SearchTopic(response) => Json(Ideas::new(response.ideas)).into_response(),
+        Response::ListTopic(response) => Json(Ideas::new(response.ideas)).into_response(),
+        Response::Error { code, message } => {
+            (status_for(code), Json(ErrorBody::new(code, message))).into_response()
+        }
+        other => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorBody::new(
+                ErrorCode::Internal,
+                format!("Unexpected response: {other:?}"),
+            )),
+        )
+            .into_response(),
+    }
+}
+
+fn status_for(code: ErrorCode) -> StatusCode {
+    match code {
+        ErrorCode::InvalidRequest => StatusCode::BAD_REQUEST,
+        ErrorCode::Storage | ErrorCode::Embedding => StatusCode::BAD_GATEWAY,
+        ErrorCode::ModelMismatch => StatusCode::CONFLICT,
+        ErrorCode::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CreateTopicResponse, SearchTopicResponse};
+    use axum::body::{Body, to_bytes};
+    use embedding::hashing::HashingEmbedder;
+    use storage_client::TopicStorage;
+    use tower::ServiceExt;
+
+    async fn test_router() -> Router {
+        // Nothing in these tests reaches Qdrant, which is only contacted on use.
+        let storage = TopicStorage::new("http://localhost:6334", HashingEmbedder::new(8))
+            .await
+            .unwrap();
+        let daemon = Arc::new(Daemon::new(storage, bincode::config::standard()));
+        router(daemon, Arc::from("secret"))
+    }
+
+    fn get(uri: &str, token: Option<&str>) -> HttpRequest {
+        let mut request = HttpRequest::get(uri);
+        if let Some(token) = token {
+            request = request.header(header::AUTHORIZATION, format!("Bearer {token}"));
+        }
+        request.body(Body::empty()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_requires_bearer_token() {
+        let router = test_router().await;
+        for token in [None, Some("guess")] {
+            let response = router
+                .clone()
+                .oneshot(get("/topics/weekend/ideas", token))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+            assert_eq!(response.headers()[header::WWW_AUTHENTICATE], "Bearer");
+        }
+
+        // A missing parameter is only noticed once the token is accepted.
+        let response = router
+            .oneshot(get("/ideas/search", Some("secret")))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_serves_openapi_document() {
+        let response = test_router()
+            .await
+            .oneshot(get("/openapi.json", None))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let document: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        for path in [
+            "/ideas",
+            "/ideas/search",
+            "/ideas/{id}",
+            "/topics/{name}/ideas",
+        ] {
+            assert!(document["paths"][path].is_object(), "{path} is missing");
+        }
+        assert_eq!(
+            document["paths"]["/ideas"]["post"]["responses"]["201"]["content"]["application/json"]
+                ["schema"]["$ref"],
+            "#/components/schemas/CreatedIdea"
+        );
+        assert!(document["components"]["schemas"]["Idea"]["properties"]["snippet"].is_object());
+        assert_eq!(
+            document["components"]["securitySchemes"]["bearer"]["scheme"],
+            "bearer"
+        );
+    }
+
+    async fn json_body(response: HttpResponse) -> serde_json::Value {
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_replies_with_ideas() {
+        let response = reply(Response::CreateTopic(CreateTopicResponse {
+            success: true,
+            idea_id: 42,
+        }));
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(json_body(response).await, serde_json::json!({ "id": 42 }));
+
+        let response = reply(Response::SearchTopic(SearchTopicResponse {
+            results: vec!["hiking in the mountains".to_string()],
+            ideas: vec![IdeaEntry {
+                id: 7,
+                topic_name: "weekend".to_string(),
+                content: "hiking in the mountains".to_string(),
+                snippet: Some("the mountains".to_string()),
+            }],
+        }));
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            json_body(response).await,
+            serde_json::json!({ "results": [{
+                "id": 7,
+                "topic": "weekend",
+                "content": "hiking in the mountains",
+                "snippet": "the mountains",
+            }] })
+        );
+    }
+
+    #[test]
+    fn test_error_status() {
+        let response = reply(Response::error(ErrorCode::Storage, "Qdrant is down"));
+        assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
+        assert_eq!(
+            reply(Response::error(ErrorCode::ModelMismatch, "")).status(),
+            StatusCode::CONFLICT
+        );
+    }
+}
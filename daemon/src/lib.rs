@@ -1,6 +1,15 @@
 use bincode::{Decode, Encode};
+use std::fmt;
+use storage_client::{Idea, TopicStorageError};
+use tracing::warn;
 
+pub mod client;
 pub mod daemon;
+pub mod http;
+pub mod metrics;
+pub mod paths;
+pub mod protocol;
+pub mod systemd;
 
 #[derive(Debug, Decode, Encode)]
 pub enum Request {
@@ -8,6 +17,13 @@ pub enum Request {
     UpdateTopic(UpdateTopicRequest),
     SearchTopic(SearchTopicRequest),
     ListTopic(ListTopicRequest),
+    DeleteIdea(DeleteIdeaRequest),
+    Status,
+    Shutdown,
+    Ping,
+    /// Check that Qdrant and the embedding provider are reachable
+    Health,
+    Info,
 }
 
 #[derive(Debug, Decode, Encode)]
@@ -16,6 +32,103 @@ pub enum Response {
     UpdateTopic(UpdateTopicResponse),
     SearchTopic(SearchTopicResponse),
     ListTopic(ListTopicResponse),
+    /// The request failed; the connection stays usable.
+    Error {
+        code: ErrorCode,
+        message: String,
+    },
+    DeleteIdea(DeleteIdeaResponse),
+    Status(StatusResponse),
+    /// The daemon stops accepting connections after sending this.
+    Shutdown,
+    Pong,
+    Health(HealthResponse),
+    Info(InfoResponse),
+}
+
+/// Broad class of a failed request, so clients can react without parsing messages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Decode, Encode)]
+pub enum ErrorCode {
+    /// The request could not be decoded or makes no sense
+    InvalidRequest,
+    /// Qdrant failed or is unreachable
+    Storage,
+    /// The embedding provider failed or is unreachable
+    Embedding,
+    /// The collection was built with a different embedding model
+    ModelMismatch,
+    Internal,
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let code = match self {
+            ErrorCode::InvalidRequest => "invalid request",
+            ErrorCode::Storage => "storage error",
+            ErrorCode::Embedding => "embedding error",
+            ErrorCode::ModelMismatch => "model mismatch",
+            ErrorCode::Internal => "internal error",
+        };
+        write!(f, "{}", code)
+    }
+}
+
+impl From<&TopicStorageError> for ErrorCode {
+    fn from(error: &TopicStorageError) -> Self {
+        match error {
+            TopicStorageError::QdrantError(_) => ErrorCode::Storage,
+            TopicStorageError::EmbeddingError(_) => ErrorCode::Embedding,
+            TopicStorageError::ReindexIntoItself(_) => ErrorCode::InvalidRequest,
+            TopicStorageError::ModelMismatch { .. } => ErrorCode::ModelMismatch,
+        }
+    }
+}
+
+impl Request {
+    /// Name of the request, for logs.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Request::CreateTopic(_) => "create_topic",
+            Request::UpdateTopic(_) => "update_topic",
+            Request::SearchTopic(_) => "search_topic",
+            Request::ListTopic(_) => "list_topic",
+            Request::DeleteIdea(_) => "delete_idea",
+            Request::Status => "status",
+            Request::Shutdown => "shutdown",
+            Request::Ping => "ping",
+            Request::Health => "health",
+            Request::Info => "info",
+        }
+    }
+
+    /// Protocol version that introduced the request; older connections may
+    /// not send it.
+    pub fn since_version(&self) -> u16 {
+        match self {
+            Request::CreateTopic(_)
+            | Request::UpdateTopic(_)
+            | Request::SearchTopic(_)
+            | Request::ListTopic(_) => 1,
+            Request::DeleteIdea(_) => 2,
+            Request::Status | Request::Shutdown => 3,
+            Request::Ping | Request::Health | Request::Info => 4,
+        }
+    }
+}
+
+impl Response {
+    pub fn error(code: ErrorCode, message: impl Into<String>) -> Self {
+        Response::Error {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+impl From<TopicStorageError> for Response {
+    fn from(error: TopicStorageError) -> Self {
+        Response::error(ErrorCode::from(&error), error.to_string())
+    }
 }
 
 #[derive(Debug, Decode, Encode)]
@@ -27,6 +140,8 @@ pub struct CreateTopicRequest {
 #[derive(Debug, Decode, Encode)]
 pub struct CreateTopicResponse {
     pub success: bool,
+    /// Id of the stored idea, since protocol version 5
+    pub idea_id: u64,
 }
 
 #[derive(Debug, Decode, Encode)]
@@ -38,6 +153,8 @@ pub struct UpdateTopicRequest {
 #[derive(Debug, Decode, Encode)]
 pub struct UpdateTopicResponse {
     pub success: bool,
+    /// Id of the stored idea, since protocol version 5
+    pub idea_id: u64,
 }
 
 #[derive(Debug, Decode, Encode)]
@@ -49,7 +166,10 @@ pub struct SearchTopicRequest {
 
 #[derive(Debug, Decode, Encode)]
 pub struct SearchTopicResponse {
+    /// Content of the found ideas, kept for clients older than version 5
     pub results: Vec<String>,
+    /// The same ideas with their ids and snippets, since protocol version 5
+    pub ideas: Vec<IdeaEntry>,
 }
 
 #[derive(Debug, Decode, Encode)]
@@ -60,5 +180,168 @@ pub struct ListTopicRequest {
 
 #[derive(Debug, Decode, Encode)]
 pub struct ListTopicResponse {
+    /// Content of the listed ideas, kept for clients older than version 5
     pub results: Vec<String>,
-}
\ No newline at end of file
+    /// The same ideas with their ids, since protocol version 5
+    pub ideas: Vec<IdeaEntry>,
+}
+
+/// An idea returned by a search or a listing
+#[derive(Debug, Clone, Decode, Encode)]
+pub struct IdeaEntry {
+    /// Id to update or delete the idea with
+    pub id: u64,
+    pub topic_name: String,
+    pub content: String,
+    /// Part of a chunked idea that matched the query
+    pub snippet: Option<String>,
+}
+
+impl IdeaEntry {
+    /// Entry for an idea read from storage, or `None` with a warning if its
+    /// point has no numeric id, as it couldn't be deleted through the entry.
+    pub fn from_stored(idea: Idea) -> Option<Self> {
+        let Some(id) = idea.id else {
+            warn!(topic = %idea.topic_name, "Skipping an idea without a numeric id");
+            return None;
+        };
+        Some(IdeaEntry {
+            id,
+            topic_name: idea.topic_name,
+            content: idea.content,
+            snippet: idea.snippet,
+        })
+    }
+}
+
+#[derive(Debug, Decode, Encode)]
+pub struct DeleteIdeaRequest {
+    pub idea_id: u64,
+}
+
+#[derive(Debug, Decode, Encode)]
+pub struct DeleteIdeaResponse {
+    pub success: bool,
+}
+
+#[derive(Debug, Decode, Encode)]
+pub struct StatusResponse {
+    pub pid: u32,
+    pub uptime_secs: u64,
+    /// Embedding model the daemon serves
+    pub provider: String,
+    pub collection: String,
+    /// Requests handled since the daemon started
+    pub requests: u64,
+    pub failed_requests: u64,
+}
+
+/// Outcome of the checks of a `Health` request
+#[derive(Debug, Decode, Encode)]
+pub struct HealthResponse {
+    /// Version of the Qdrant server, or why it can't be reached
+    pub qdrant: Result<String, String>,
+    /// Why the embedding provider failed its probe, if it did
+    pub embedding: Result<(), String>,
+}
+
+impl HealthResponse {
+    pub fn is_healthy(&self) -> bool {
+        self.qdrant.is_ok() && self.embedding.is_ok()
+    }
+}
+
+#[derive(Debug, Decode, Encode)]
+pub struct InfoResponse {
+    /// Newest protocol version the daemon speaks
+    pub protocol_version: u16,
+    pub min_protocol_version: u16,
+    /// Id of the embedding model
+    pub provider: String,
+    pub dimension: u64,
+    pub collection: String,
+    /// `None` if the collection doesn't exist yet or Qdrant can't be reached
+    pub points: Option<u64>,
+    /// Words per chunk, `None` if ideas aren't split; since protocol version 6
+    pub chunk_words: Option<u64>,
+    pub chunk_overlap: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_storage_errors_become_error_responses() {
+        let config = bincode::config::standard();
+        let response = Response::from(TopicStorageError::ReindexIntoItself("ideas".to_string()));
+        let encoded = bincode::encode_to_vec(&response, config).unwrap();
+
+        match bincode::decode_from_slice(&encoded, config).unwrap().0 {
+            Response::Error { code, message } => {
+                assert_eq!(code, ErrorCode::InvalidRequest);
+                assert!(message.contains("'ideas'"));
+            }
+            response => panic!("Unexpected response {:?}", response),
+        }
+    }
+
+    #[test]
+    fn test_ideas_without_id_are_skipped() {
+        let idea = |id| Idea::new("weekend".to_string(), "hiking".to_string(), id);
+        assert_eq!(IdeaEntry::from_stored(idea(Some(7))).unwrap().id, 7);
+        assert!(IdeaEntry::from_stored(idea(None)).is_none());
+    }
+
+    #[test]
+    fn test_error_wire_index() {
+        // Peers of every version decode errors by this index; it must not move.
+        let config = bincode::config::standard();
+        let encoded =
+            bincode::encode_to_vec(Response::error(ErrorCode::Internal, ""), config).unwrap();
+        assert_eq!(encoded[0], 4);
+    }
+
+    #[test]
+    fn test_health_response_roundtrip() {
+        let config = bincode::config::standard();
+        let response = Response::Health(HealthResponse {
+            qdrant: Err("Qdrant error: connection refused".to_string()),
+            embedding: Ok(()),
+        });
+        let encoded = bincode::encode_to_vec(&response, config).unwrap();
+
+        match bincode::decode_from_slice(&encoded, config).unwrap().0 {
+            Response::Health(health) => {
+                assert!(!health.is_healthy());
+                assert!(health.qdrant.unwrap_err().contains("connection refused"));
+            }
+            response => panic!("Unexpected response {:?}", response),
+        }
+    }
+
+    #[test]
+    fn test_older_clients_read_search_responses() {
+        /// `SearchTopicResponse` as protocol version 4 knew it
+        #[derive(Decode)]
+        struct SearchTopicResponseV4 {
+            results: Vec<String>,
+        }
+
+        let config = bincode::config::standard();
+        let response = SearchTopicResponse {
+            results: vec!["hiking in the mountains".to_string()],
+            ideas: vec![IdeaEntry {
+                id: 7,
+                topic_name: "weekend".to_string(),
+                content: "hiking in the mountains".to_string(),
+                snippet: Some("mountains".to_string()),
+            }],
+        };
+        let encoded = bincode::encode_to_vec(&response, config).unwrap();
+
+        let (old, _): (SearchTopicResponseV4, _) =
+            bincode::decode_from_slice(&encoded, config).unwrap();
+        assert_eq!(old.results, response.results);
+    }
+}
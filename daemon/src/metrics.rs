@@ -0,0 +1,54 @@
+//! Prometheus metrics of the daemon, served over HTTP on request.
+//!
+//! Besides the request metrics below, the exporter serves those of
+//! [`storage_client::metrics`], where `qdrant_cli_embedding_words_total`
+//! stands in for the tokens sent to the embedding provider. Nothing is
+//! cached, so there is no cache hit metric.
+
+use eyre::{Result, WrapErr};
+use metrics::{Unit, counter, describe_counter, describe_histogram, histogram};
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder};
+use std::{net::SocketAddr, time::Duration};
+
+/// Requests answered, labeled by `kind`.
+pub const REQUESTS: &str = "qdrant_cli_daemon_requests_total";
+/// Requests that failed, labeled by `kind`.
+pub const REQUEST_ERRORS: &str = "qdrant_cli_daemon_request_errors_total";
+/// Time spent processing requests, labeled by `kind`.
+pub const REQUEST_DURATION: &str = "qdrant_cli_daemon_request_duration_seconds";
+
+/// Histogram buckets in seconds, from a cached Qdrant lookup to a slow
+/// embedding API.
+const BUCKETS: &[f64] = &[
+    0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// Install the global metrics recorder and serve its metrics in the
+/// Prometheus text format on `http://{addr}/metrics`.
+///
+/// Must be called from within the tokio runtime, which runs the listener.
+pub fn install_exporter(addr: SocketAddr) -> Result<()> {
+    PrometheusBuilder::new()
+        .with_http_listener(addr)
+        .set_buckets_for_metric(Matcher::Suffix("_seconds".to_string()), BUCKETS)?
+        .install()
+        .wrap_err_with(|| format!("Failed to serve metrics on {addr}"))?;
+
+    describe_counter!(REQUESTS, "Requests answered by the daemon");
+    describe_counter!(REQUEST_ERRORS, "Requests the daemon failed");
+    describe_histogram!(
+        REQUEST_DURATION,
+        Unit::Seconds,
+        "Time spent processing requests"
+    );
+    storage_client::metrics::describe();
+    Ok(())
+}
+
+pub(crate) fn record_request(kind: &'static str, duration: Duration, success: bool) {
+    counter!(REQUESTS, "kind" => kind).increment(1);
+    histogram!(REQUEST_DURATION, "kind" => kind).record(duration);
+    if !success {
+        counter!(REQUEST_ERRORS, "kind" => kind).increment(1);
+    }
+}
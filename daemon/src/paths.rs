@@ -0,0 +1,51 @@
+use nix::{sys::signal::kill, unistd::Pid};
+use std::{fs, io, path::PathBuf};
+
+const APP_DIR: &str = "qdrant-cli";
+
+/// Files of the daemon.
+///
+/// The pid file lives in `$XDG_RUNTIME_DIR/qdrant-cli`, which is cleared on
+/// logout, and the logs in `$XDG_STATE_HOME/qdrant-cli`. Without a runtime
+/// directory the pid file goes next to the logs. Settings that SIGHUP reloads
+/// are read from `$XDG_CONFIG_HOME/qdrant-cli/daemon.conf`.
+#[derive(Debug, Clone)]
+pub struct DaemonPaths {
+    pub pid_file: PathBuf,
+    /// Directory of the daemon's daily log files
+    pub log_dir: PathBuf,
+    /// Optional; not created by the daemon
+    pub config_file: PathBuf,
+}
+
+impl DaemonPaths {
+    /// Resolve the paths from the environment, creating their directories.
+    pub fn from_env() -> io::Result<Self> {
+        let state_dir = dirs::state_dir()
+            .unwrap_or_else(std::env::temp_dir)
+            .join(APP_DIR);
+        let runtime_dir = dirs::runtime_dir()
+            .map(|dir| dir.join(APP_DIR))
+            .unwrap_or_else(|| state_dir.clone());
+        let config_dir = dirs::config_dir()
+            .map(|dir| dir.join(APP_DIR))
+            .unwrap_or_else(|| state_dir.clone());
+        fs::create_dir_all(&state_dir)?;
+        fs::create_dir_all(&runtime_dir)?;
+        Ok(Self {
+            pid_file: runtime_dir.join("daemon.pid"),
+            log_dir: state_dir,
+            config_file: config_dir.join("daemon.conf"),
+        })
+    }
+
+    /// Pid of the daemon, if the pid file names a live process.
+    pub fn running_pid(&self) -> Option<i32> {
+        let pid = fs::read_to_string(&self.pid_file)
+            .ok()?
+            .trim()
+            .parse()
+            .ok()?;
+        kill(Pid::from_raw(pid), None).is_ok().then_some(pid)
+    }
+}
@@ -0,0 +1,239 @@
+//! Wire protocol between the daemon and its clients.
+//!
+//! A connection starts with a handshake: the client sends [`MAGIC`] followed
+//! by the oldest and newest protocol versions it speaks, and the daemon
+//! answers with [`MAGIC`], its own range and the negotiated version, which is
+//! the newest version both sides speak, or `0` when the ranges don't overlap.
+//! After that every message is a bincode payload prefixed with its length as
+//! a big-endian `u32`.
+//!
+//! Compatibility rules: appending a `Request`/`Response` variant or a trailing
+//! field bumps [`PROTOCOL_VERSION`], and a daemon never sends a client
+//! anything newer than the negotiated version: requests introduced later, as
+//! told by `Request::since_version`, are answered with an `InvalidRequest`
+//! error. Trailing fields are sent regardless, as decoding ignores the bytes
+//! after a message, so clients only rely on them once the negotiated version
+//! has them. Changing or removing anything
+//! also bumps [`MIN_PROTOCOL_VERSION`], so old peers are refused during the
+//! handshake instead of misreading messages.
+
+use bincode::{Decode, Encode, config::Config};
+use std::{io, ops::RangeInclusive};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+pub const MAGIC: [u8; 4] = *b"QDRD";
+/// Version 1 includes `Response::Error`, which was appended before any
+/// release spoke the protocol. Version 2 added `Request::DeleteIdea`,
+/// version 3 `Request::Status` and `Request::Shutdown`, version 4
+/// `Request::Ping`, `Request::Health` and `Request::Info`, version 5 the ids
+/// and snippets of ideas to the responses of the create, update, search and
+/// list requests, version 6 the chunking settings to `InfoResponse`.
+pub const PROTOCOL_VERSION: u16 = 6;
+/// Oldest version whose responses carry the ids of ideas.
+pub const IDEA_IDS_VERSION: u16 = 5;
+/// Oldest version whose info responses carry the chunking settings.
+pub const INFO_CHUNKING_VERSION: u16 = 6;
+pub const MIN_PROTOCOL_VERSION: u16 = 1;
+/// Frames larger than this are rejected rather than allocated.
+pub const MAX_FRAME_SIZE: usize = 16 * 1024 * 1024;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ProtocolError {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+    #[error("Peer does not speak the qdrant-cli daemon protocol")]
+    BadMagic,
+    #[error(
+        "Incompatible protocol versions: client speaks {}-{}, daemon speaks {}-{}",
+        client.start(), client.end(), daemon.start(), daemon.end()
+    )]
+    IncompatibleVersion {
+        client: RangeInclusive<u16>,
+        daemon: RangeInclusive<u16>,
+    },
+    #[error("Frame of {0} bytes exceeds the maximum frame size")]
+    FrameTooLarge(usize),
+    #[error("Failed to encode message: {0}")]
+    Encode(#[from] bincode::error::EncodeError),
+    #[error("Failed to decode message: {0}")]
+    Decode(#[from] bincode::error::DecodeError),
+}
+
+type Result<T> = std::result::Result<T, ProtocolError>;
+
+fn supported_versions() -> RangeInclusive<u16> {
+    MIN_PROTOCOL_VERSION..=PROTOCOL_VERSION
+}
+
+async fn read_header(reader: &mut (impl AsyncRead + Unpin)) -> Result<RangeInclusive<u16>> {
+    let mut magic = [0; 4];
+    reader.read_exact(&mut magic).await?;
+    if magic != MAGIC {
+        return Err(ProtocolError::BadMagic);
+    }
+    Ok(reader.read_u16().await?..=reader.read_u16().await?)
+}
+
+async fn write_header(
+    writer: &mut (impl AsyncWrite + Unpin),
+    versions: &RangeInclusive<u16>,
+) -> Result<()> {
+    writer.write_all(&MAGIC).await?;
+    writer.write_u16(*versions.start()).await?;
+    writer.write_u16(*versions.end()).await?;
+    Ok(())
+}
+
+/// Newest version both ranges contain.
+fn negotiate(client: &RangeInclusive<u16>, daemon: &RangeInclusive<u16>) -> Option<u16> {
+    let version = *client.end().min(daemon.end());
+    (version >= *client.start().max(daemon.start())).then_some(version)
+}
+
+/// Open a connection from the client side, returning the negotiated version.
+pub async fn client_handshake(
+    reader: &mut (impl AsyncRead + Unpin),
+    writer: &mut (impl AsyncWrite + Unpin),
+) -> Result<u16> {
+    let client = supported_versions();
+    write_header(writer, &client).await?;
+    writer.flush().await?;
+
+    let daemon = read_header(reader).await?;
+    match reader.read_u16().await? {
+        0 => Err(ProtocolError::IncompatibleVersion { client, daemon }),
+        version => Ok(version),
+    }
+}
+
+/// Accept a connection on the daemon side, returning the negotiated version.
+///
+/// Incompatible clients are told the daemon's versions before the error is
+/// returned, so they can report a meaningful message.
+pub async fn server_handshake(
+    reader: &mut (impl AsyncRead + Unpin),
+    writer: &mut (impl AsyncWrite + Unpin),
+) -> Result<u16> {
+    let client = read_header(reader).await?;
+    let daemon = supported_versions();
+    let version = negotiate(&client, &daemon);
+
+    write_header(writer, &daemon).await?;
+    writer.write_u16(version.unwrap_or(0)).await?;
+    writer.flush().await?;
+    version.ok_or(ProtocolError::IncompatibleVersion { client, daemon })
+}
+
+/// Read one frame, or `None` if the peer closed the connection between frames.
+pub async fn read_frame(reader: &mut (impl AsyncRead + Unpin)) -> Result<Option<Vec<u8>>> {
+    let len = match reader.read_u32().await {
+        Ok(len) => len as usize,
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    if len > MAX_FRAME_SIZE {
+        return Err(ProtocolError::FrameTooLarge(len));
+    }
+    let mut payload = vec![0; len];
+    reader.read_exact(&mut payload).await?;
+    Ok(Some(payload))
+}
+
+pub async fn write_frame(writer: &mut (impl AsyncWrite + Unpin), payload: &[u8]) -> Result<()> {
+    if payload.len() > MAX_FRAME_SIZE {
+        return Err(ProtocolError::FrameTooLarge(payload.len()));
+    }
+    writer.write_u32(payload.len() as u32).await?;
+    writer.write_all(payload).await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+/// Decode the next message, or `None` if the peer closed the connection.
+pub async fn recv<M: Decode<()>>(
+    reader: &mut (impl AsyncRead + Unpin),
+    config: impl Config,
+) -> Result<Option<M>> {
+    match read_frame(reader).await? {
+        Some(payload) => Ok(Some(bincode::decode_from_slice(&payload, config)?.0)),
+        None => Ok(None),
+    }
+}
+
+pub async fn send<M: Encode>(
+    writer: &mut (impl AsyncWrite + Unpin),
+    message: &M,
+    config: impl Config,
+) -> Result<()> {
+    write_frame(writer, &bincode::encode_to_vec(message, config)?).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ListTopicRequest, Request};
+
+    #[test]
+    fn test_negotiate() {
+        assert_eq!(negotiate(&(1..=3), &(2..=5)), Some(3));
+        assert_eq!(negotiate(&(1..=2), &(1..=1)), Some(1));
+        assert_eq!(negotiate(&(1..=1), &(2..=3)), None);
+    }
+
+    #[tokio::test]
+    async fn test_handshake_and_frames() {
+        let (client, server) = tokio::io::duplex(64);
+        let (mut client_reader, mut client_writer) = tokio::io::split(client);
+        let (mut server_reader, mut server_writer) = tokio::io::split(server);
+        let config = bincode::config::standard();
+
+        let server = tokio::spawn(async move {
+            server_handshake(&mut server_reader, &mut server_writer)
+                .await
+                .unwrap();
+            let request: Request = recv(&mut server_reader, config).await.unwrap().unwrap();
+            assert!(
+                recv::<Request>(&mut server_reader, config)
+                    .await
+                    .unwrap()
+                    .is_none()
+            );
+            request
+        });
+
+        let version = client_handshake(&mut client_reader, &mut client_writer)
+            .await
+            .unwrap();
+        assert_eq!(version, PROTOCOL_VERSION);
+        let request = Request::ListTopic(ListTopicRequest {
+            topic_name: "hiking".to_string(),
+            limit: 5,
+        });
+        send(&mut client_writer, &request, config).await.unwrap();
+        client_writer.shutdown().await.unwrap();
+
+        match server.await.unwrap() {
+            Request::ListTopic(request) => assert_eq!(request.topic_name, "hiking"),
+            request => panic!("Unexpected request {:?}", request),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rejects_foreign_peers() {
+        let (client, mut server) = tokio::io::duplex(64);
+        let (mut reader, mut writer) = tokio::io::split(client);
+        server.write_all(b"HTTP/1.1 400").await.unwrap();
+
+        let result = client_handshake(&mut reader, &mut writer).await;
+        assert!(matches!(result, Err(ProtocolError::BadMagic)));
+    }
+
+    #[tokio::test]
+    async fn test_rejects_oversized_frames() {
+        let (mut reader, mut writer) = tokio::io::duplex(64);
+        writer.write_u32(u32::MAX).await.unwrap();
+
+        let result = read_frame(&mut reader).await;
+        assert!(matches!(result, Err(ProtocolError::FrameTooLarge(_))));
+    }
+}
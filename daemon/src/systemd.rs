@@ -0,0 +1,150 @@
+//! Integration with systemd: socket activation, readiness and watchdog
+//! notifications, and the user units that start the daemon on demand.
+//!
+//! Everything here is a no-op when the daemon isn't run by systemd.
+
+use sd_notify::NotifyState;
+use std::{
+    fs, io,
+    os::fd::{FromRawFd, OwnedFd},
+    path::PathBuf,
+    time::Duration,
+};
+
+use crate::daemon::{DaemonSocket, PRINT_NAME};
+
+/// Name of the generated units, without the `.service`/`.socket` suffix.
+pub const UNIT_NAME: &str = "qdrant-cli-daemon";
+/// Watchdog timeout written to the service unit.
+pub const WATCHDOG_SEC: u64 = 30;
+
+/// Listening socket passed by systemd socket activation (`LISTEN_FDS`), if any.
+///
+/// Clears the activation variables, so call this once before any other
+/// thread is started.
+pub fn activation_socket() -> io::Result<Option<OwnedFd>> {
+    let mut fds = sd_notify::listen_fds()?;
+    let fd = fds.next();
+    if fds.next().is_some() {
+        tracing::warn!("systemd passed several sockets, only the first one is used");
+    }
+    // SAFETY: systemd hands the activation sockets over to this process, and
+    // `listen_fds` only yields each of them once.
+    Ok(fd.map(|fd| unsafe { OwnedFd::from_raw_fd(fd) }))
+}
+
+/// Tell systemd the daemon is listening.
+pub fn notify_ready() {
+    notify(&[NotifyState::Ready]);
+}
+
+/// Tell systemd the daemon is reloading its configuration.
+pub fn notify_reloading() {
+    match NotifyState::monotonic_usec_now() {
+        Ok(now) => notify(&[NotifyState::Reloading, now]),
+        Err(_) => notify(&[NotifyState::Reloading]),
+    }
+}
+
+/// Tell systemd the daemon is draining connections before it exits.
+pub fn notify_stopping() {
+    notify(&[NotifyState::Stopping]);
+}
+
+pub fn notify_watchdog() {
+    notify(&[NotifyState::Watchdog]);
+}
+
+fn notify(state: &[NotifyState]) {
+    if let Err(e) = sd_notify::notify(false, state) {
+        tracing::warn!("Failed to notify systemd: {e}");
+    }
+}
+
+/// How often to ping the systemd watchdog: half its timeout, if it is enabled.
+pub fn watchdog_interval() -> Option<Duration> {
+    let mut usec = 0;
+    sd_notify::watchdog_enabled(false, &mut usec).then(|| Duration::from_micros(usec) / 2)
+}
+
+/// Socket unit listening on the daemon socket, so connecting starts the daemon.
+pub fn socket_unit(socket: &DaemonSocket) -> String {
+    let listen = match socket {
+        DaemonSocket::Namespaced => format!("ListenStream=@{PRINT_NAME}"),
+        DaemonSocket::Path(path) => format!("ListenStream={}\nSocketMode=0600", path.display()),
+    };
+    format!(
+        "[Unit]
+Description=qdrant-cli daemon socket
+
+[Socket]
+{listen}
+
+[Install]
+WantedBy=sockets.target
+"
+    )
+}
+
+/// Service unit running `exec_start`, which must keep the daemon in the
+/// foreground.
+pub fn service_unit(exec_start: &str) -> String {
+    format!(
+        "[Unit]
+Description=qdrant-cli daemon
+Requires={UNIT_NAME}.socket
+After={UNIT_NAME}.socket network-online.target
+
+[Service]
+Type=notify
+ExecStart={exec_start}
+ExecReload=/bin/kill -HUP $MAINPID
+WatchdogSec={WATCHDOG_SEC}
+Restart=on-failure
+
+[Install]
+Also={UNIT_NAME}.socket
+WantedBy=default.target
+"
+    )
+}
+
+/// Write the service and socket units to the systemd user unit directory,
+/// replacing earlier ones, and return that directory.
+pub fn install_units(exec_start: &str, socket: &DaemonSocket) -> io::Result<PathBuf> {
+    let unit_dir = dirs::config_dir()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "No configuration directory"))?
+        .join("systemd")
+        .join("user");
+    fs::create_dir_all(&unit_dir)?;
+    fs::write(
+        unit_dir.join(format!("{UNIT_NAME}.service")),
+        service_unit(exec_start),
+    )?;
+    fs::write(
+        unit_dir.join(format!("{UNIT_NAME}.socket")),
+        socket_unit(socket),
+    )?;
+    Ok(unit_dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_units_reference_each_other() {
+        let service = service_unit("/usr/bin/qdrant-cli daemon start --foreground");
+        assert!(service.contains("ExecStart=/usr/bin/qdrant-cli daemon start --foreground\n"));
+        assert!(service.contains(&format!("Requires={UNIT_NAME}.socket\n")));
+        assert!(
+            socket_unit(&DaemonSocket::Namespaced)
+                .contains(&format!("ListenStream=@{PRINT_NAME}\n"))
+        );
+
+        let socket = socket_unit(&DaemonSocket::Path(PathBuf::from(
+            "/run/user/1000/qdrant.sock",
+        )));
+        assert!(socket.contains("ListenStream=/run/user/1000/qdrant.sock\nSocketMode=0600\n"));
+    }
+}
@@ -0,0 +1,187 @@
+use eyre::{WrapErr, eyre};
+pub use secrecy::{ExposeSecret, SecretString};
+use std::{
+    ffi::OsString,
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+/// Environment variable the Hugging Face tooling reads the API key from.
+pub const TOKEN_ENV: &str = "HF_TOKEN";
+pub const KEYRING_SERVICE: &str = "qdrant-cli";
+pub const KEYRING_USER: &str = "hugging-face";
+
+/// Where an API key was found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ApiKeySource {
+    Argument,
+    File(PathBuf),
+    Environment,
+    TokenCache(PathBuf),
+    Keyring,
+}
+
+impl fmt::Display for ApiKeySource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApiKeySource::Argument => write!(f, "command line argument"),
+            ApiKeySource::File(path) => write!(f, "file {}", path.display()),
+            ApiKeySource::Environment => write!(f, "{} environment variable", TOKEN_ENV),
+            ApiKeySource::TokenCache(path) => write!(f, "token cache {}", path.display()),
+            ApiKeySource::Keyring => {
+                write!(f, "keyring entry {}/{}", KEYRING_SERVICE, KEYRING_USER)
+            }
+        }
+    }
+}
+
+/// Hugging Face API key lookup.
+///
+/// Sources are tried in order: an explicitly passed key, an explicitly passed
+/// key file, the `HF_TOKEN` environment variable, the token cache written by
+/// `huggingface-cli login` and, with the `keyring` feature, the Secret Service
+/// entry `qdrant-cli`/`hugging-face`. The key never leaves a [`SecretString`],
+/// so it is redacted from `Debug` output.
+#[derive(Debug, Default)]
+pub struct ApiKeyResolver {
+    api_key: Option<SecretString>,
+    file: Option<PathBuf>,
+}
+
+impl ApiKeyResolver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_api_key(mut self, api_key: Option<SecretString>) -> Self {
+        self.api_key = api_key;
+        self
+    }
+
+    pub fn with_file(mut self, file: Option<PathBuf>) -> Self {
+        self.file = file;
+        self
+    }
+
+    pub async fn resolve(self) -> eyre::Result<(SecretString, ApiKeySource)> {
+        if let Some(api_key) = self.api_key {
+            return Ok((api_key, ApiKeySource::Argument));
+        }
+        if let Some(path) = self.file {
+            let api_key = read_key_file(&path)?
+                .ok_or_else(|| eyre!("API key file {} is empty", path.display()))?;
+            return Ok((api_key, ApiKeySource::File(path)));
+        }
+        if let Some(api_key) = std::env::var(TOKEN_ENV).ok().and_then(non_empty) {
+            return Ok((api_key, ApiKeySource::Environment));
+        }
+        if let Some(path) = token_cache_path(|key| std::env::var_os(key))
+            && path.is_file()
+            && let Some(api_key) = read_key_file(&path)?
+        {
+            return Ok((api_key, ApiKeySource::TokenCache(path)));
+        }
+        #[cfg(feature = "keyring")]
+        if let Some(api_key) = read_keyring().await? {
+            return Ok((api_key, ApiKeySource::Keyring));
+        }
+        Err(eyre!(
+            "No Hugging Face API key found in a key file, {}, the token cache or the keyring",
+            TOKEN_ENV
+        ))
+    }
+}
+
+fn non_empty(key: String) -> Option<SecretString> {
+    let key = key.trim();
+    (!key.is_empty()).then(|| SecretString::from(key))
+}
+
+fn read_key_file(path: &Path) -> eyre::Result<Option<SecretString>> {
+    let contents = fs::read_to_string(path)
+        .wrap_err_with(|| format!("Failed to read API key from {}", path.display()))?;
+    Ok(non_empty(contents))
+}
+
+/// Location of the token written by `huggingface-cli login`, honouring the
+/// same environment variables as the Hugging Face tooling.
+fn token_cache_path(var: impl Fn(&str) -> Option<OsString>) -> Option<PathBuf> {
+    if let Some(path) = var("HF_TOKEN_PATH") {
+        return Some(PathBuf::from(path));
+    }
+    let hf_home = var("HF_HOME").map(PathBuf::from).or_else(|| {
+        var("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .or_else(|| var("HOME").map(|home| Path::new(&home).join(".cache")))
+            .map(|cache| cache.join("huggingface"))
+    })?;
+    Some(hf_home.join("token"))
+}
+
+#[cfg(feature = "keyring")]
+async fn read_keyring() -> eyre::Result<Option<SecretString>> {
+    // The Secret Service client blocks on D-Bus calls.
+    let password = tokio::task::spawn_blocking(|| {
+        keyring::Entry::new(KEYRING_SERVICE, KEYRING_USER)?.get_password()
+    })
+    .await
+    .wrap_err("Keyring lookup panicked")?;
+    match password {
+        Ok(password) => Ok(non_empty(password)),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        // No Secret Service is running, which is common on headless systems;
+        // other sources may still have the key.
+        Err(keyring::Error::PlatformFailure(e)) => {
+            tracing::warn!("Skipping the keyring, which is unavailable: {e}");
+            Ok(None)
+        }
+        Err(e) => Err(e).wrap_err("Failed to read API key from the keyring"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_cache_path() {
+        let vars = |pairs: &'static [(&'static str, &'static str)]| {
+            move |key: &str| {
+                pairs
+                    .iter()
+                    .find(|(name, _)| *name == key)
+                    .map(|(_, value)| OsString::from(value))
+            }
+        };
+        assert_eq!(
+            token_cache_path(vars(&[("HOME", "/home/me")])),
+            Some(PathBuf::from("/home/me/.cache/huggingface/token"))
+        );
+        assert_eq!(
+            token_cache_path(vars(&[("HOME", "/home/me"), ("HF_HOME", "/hf")])),
+            Some(PathBuf::from("/hf/token"))
+        );
+        assert_eq!(
+            token_cache_path(vars(&[("HF_TOKEN_PATH", "/secrets/hf")])),
+            Some(PathBuf::from("/secrets/hf"))
+        );
+        assert_eq!(token_cache_path(vars(&[])), None);
+    }
+
+    #[tokio::test]
+    async fn test_key_file_is_trimmed_and_redacted() {
+        let path = std::env::temp_dir().join(format!("hf-token-{}", std::process::id()));
+        fs::write(&path, "hf_secret\n").unwrap();
+
+        let (api_key, source) = ApiKeyResolver::new()
+            .with_file(Some(path.clone()))
+            .resolve()
+            .await
+            .unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(api_key.expose_secret(), "hf_secret");
+        assert_eq!(source, ApiKeySource::File(path));
+        assert!(!format!("{:?}", api_key).contains("hf_secret"));
+    }
+}
@@ -0,0 +1,244 @@
+use super::{EmbeddingError, EmbeddingModel, ModelInfo};
+use eyre::eyre;
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+pub const DEFAULT_FAILURE_THRESHOLD: u32 = 3;
+pub const DEFAULT_COOLDOWN: Duration = Duration::from_secs(30);
+
+type EmbedFuture<'a, R> = Pin<Box<dyn Future<Output = Result<R, EmbeddingError>> + Send + 'a>>;
+
+#[derive(Default)]
+struct Health {
+    consecutive_failures: u32,
+    /// While set and in the future the circuit is open and the provider is skipped.
+    open_until: Option<Instant>,
+}
+
+struct Member {
+    model: Box<dyn EmbeddingModel>,
+    health: Mutex<Health>,
+}
+
+impl Member {
+    fn is_available(&self, now: Instant) -> bool {
+        let health = self.health.lock().unwrap();
+        health.open_until.is_none_or(|open_until| open_until <= now)
+    }
+
+    fn record_success(&self) {
+        *self.health.lock().unwrap() = Health::default();
+    }
+
+    fn record_failure(&self, failure_threshold: u32, cooldown: Duration) {
+        let mut health = self.health.lock().unwrap();
+        health.consecutive_failures += 1;
+        if health.consecutive_failures >= failure_threshold {
+            health.open_until = Some(Instant::now() + cooldown);
+        }
+    }
+}
+
+/// Embedding models tried in priority order.
+///
+/// A provider failing `failure_threshold` times in a row is skipped for
+/// `cooldown` (its circuit is open), after which it gets another chance. All
+/// providers must serve the same model, since mixing embeddings of different
+/// models in one collection makes them incomparable.
+pub struct FallbackEmbedder {
+    members: Vec<Member>,
+    failure_threshold: u32,
+    cooldown: Duration,
+}
+
+impl FallbackEmbedder {
+    pub fn new(models: Vec<Box<dyn EmbeddingModel>>) -> eyre::Result<Self> {
+        let primary = models
+            .first()
+            .ok_or_else(|| eyre!("A fallback chain needs at least one provider"))?
+            .model_info();
+        if let Some(incompatible) = models
+            .iter()
+            .map(|model| model.model_info())
+            .find(|model| *model != primary)
+        {
+            return Err(eyre!(
+                "Provider serving {} cannot fall back to {}",
+                primary,
+                incompatible
+            ));
+        }
+
+        Ok(Self {
+            members: models
+                .into_iter()
+                .map(|model| Member {
+                    model,
+                    health: Mutex::default(),
+                })
+                .collect(),
+            failure_threshold: DEFAULT_FAILURE_THRESHOLD,
+            cooldown: DEFAULT_COOLDOWN,
+        })
+    }
+
+    pub fn with_failure_threshold(mut self, failure_threshold: u32) -> Self {
+        self.failure_threshold = failure_threshold.max(1);
+        self
+    }
+
+    pub fn with_cooldown(mut self, cooldown: Duration) -> Self {
+        self.cooldown = cooldown;
+        self
+    }
+
+    /// Run `call` against the first provider that succeeds.
+    ///
+    /// Providers with an open circuit are skipped, unless every circuit is
+    /// open, in which case all of them are tried anyway.
+    async fn first_success<'a, R>(
+        &'a self,
+        call: impl Fn(&'a dyn EmbeddingModel) -> EmbedFuture<'a, R>,
+    ) -> Result<R, EmbeddingError> {
+        let now = Instant::now();
+        let mut candidates: Vec<&Member> = self
+            .members
+            .iter()
+            .filter(|member| member.is_available(now))
+            .collect();
+        if candidates.is_empty() {
+            candidates = self.members.iter().collect();
+        }
+
+        let mut errors = Vec::with_capacity(candidates.len());
+        for member in candidates {
+            match call(member.model.as_ref()).await {
+                Ok(result) => {
+                    member.record_success();
+                    return Ok(result);
+                }
+                Err(e) => {
+                    tracing::warn!(
+                        model = %member.model.model_info().id,
+                        "Embedding provider failed, trying the next one: {e}"
+                    );
+                    member.record_failure(self.failure_threshold, self.cooldown);
+                    errors.push(e.to_string());
+                }
+            }
+        }
+        Err(EmbeddingError::RequestError(eyre!(
+            "All embedding providers failed: {}",
+            errors.join("; ")
+        )))
+    }
+}
+
+#[async_trait::async_trait]
+impl EmbeddingModel for FallbackEmbedder {
+    fn model_info(&self) -> &ModelInfo {
+        self.members[0].model.model_info()
+    }
+
+    async fn embed(&self, input: &str) -> Result<Vec<f32>, EmbeddingError> {
+        self.first_success(|model| model.embed(input)).await
+    }
+
+    async fn embed_batch(&self, inputs: &[&str]) -> Result<Vec<Vec<f32>>, EmbeddingError> {
+        self.first_success(|model| model.embed_batch(inputs)).await
+    }
+
+    async fn embed_query(&self, input: &str) -> Result<Vec<f32>, EmbeddingError> {
+        self.first_success(|model| model.embed_query(input)).await
+    }
+
+    async fn embed_document(&self, input: &str) -> Result<Vec<f32>, EmbeddingError> {
+        self.first_success(|model| model.embed_document(input))
+            .await
+    }
+
+    async fn embed_documents(&self, inputs: &[&str]) -> Result<Vec<Vec<f32>>, EmbeddingError> {
+        self.first_success(|model| model.embed_documents(inputs))
+            .await
+    }
+
+    async fn validate(&self) -> Result<(), EmbeddingError> {
+        self.first_success(|model| model.validate()).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    };
+
+    struct Stub {
+        model: ModelInfo,
+        fail: bool,
+        calls: Arc<AtomicUsize>,
+    }
+
+    fn stub(id: &str, fail: bool) -> (Box<dyn EmbeddingModel>, Arc<AtomicUsize>) {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let stub = Stub {
+            model: ModelInfo::new(id, 2),
+            fail,
+            calls: Arc::clone(&calls),
+        };
+        (Box::new(stub), calls)
+    }
+
+    #[async_trait::async_trait]
+    impl EmbeddingModel for Stub {
+        fn model_info(&self) -> &ModelInfo {
+            &self.model
+        }
+
+        async fn embed(&self, _input: &str) -> Result<Vec<f32>, EmbeddingError> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            if self.fail {
+                Err(EmbeddingError::RequestError(eyre!("provider is down")))
+            } else {
+                Ok(vec![1.0, 0.0])
+            }
+        }
+    }
+
+    #[test]
+    fn test_rejects_incompatible_models() {
+        let result = FallbackEmbedder::new(vec![stub("a", false).0, stub("b", false).0]);
+        assert!(result.is_err());
+        assert!(FallbackEmbedder::new(vec![]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_falls_back_and_opens_circuit() {
+        let (primary, primary_calls) = stub("m", true);
+        let (secondary, secondary_calls) = stub("m", false);
+        let fallback = FallbackEmbedder::new(vec![primary, secondary])
+            .unwrap()
+            .with_failure_threshold(2)
+            .with_cooldown(Duration::from_secs(60));
+
+        for _ in 0..3 {
+            assert_eq!(fallback.embed("idea").await.unwrap(), vec![1.0, 0.0]);
+        }
+
+        // The primary is skipped once its circuit opened after two failures.
+        assert_eq!(primary_calls.load(Ordering::SeqCst), 2);
+        assert_eq!(secondary_calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_all_failing() {
+        let fallback = FallbackEmbedder::new(vec![stub("m", true).0, stub("m", true).0]).unwrap();
+        assert!(fallback.embed("idea").await.is_err());
+    }
+}
@@ -0,0 +1,119 @@
+use super::{EmbeddingError, EmbeddingModel, ModelInfo};
+use std::ops::RangeInclusive;
+
+pub const DEFAULT_DIMENSION: usize = 384;
+
+const WORD_WEIGHT: f32 = 1.0;
+const CHAR_NGRAM_WEIGHT: f32 = 0.5;
+
+/// Deterministic embedder based on feature hashing.
+///
+/// Lowercased words and character n-grams of each word are hashed into a
+/// fixed number of buckets with a random sign, and the result is L2
+/// normalized. Texts sharing words or word fragments end up close to each
+/// other, which is enough for tests, demos and air-gapped use without any
+/// model files or network access.
+pub struct HashingEmbedder {
+    model: ModelInfo,
+    char_ngrams: RangeInclusive<usize>,
+}
+
+impl HashingEmbedder {
+    pub fn new(dimension: usize) -> Self {
+        Self::with_char_ngrams(dimension, 3..=5)
+    }
+
+    pub fn with_char_ngrams(dimension: usize, char_ngrams: RangeInclusive<usize>) -> Self {
+        let id = format!(
+            "hashing-fnv1a-c{}-{}",
+            char_ngrams.start(),
+            char_ngrams.end()
+        );
+        Self {
+            model: ModelInfo::new(id, dimension.max(1)),
+            char_ngrams,
+        }
+    }
+
+    pub fn embed_sync(&self, input: &str) -> Vec<f32> {
+        let mut embedding = vec![0.0; self.model.dimension];
+        let text = input.to_lowercase();
+
+        for word in text
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+        {
+            self.add_feature(&mut embedding, word.as_bytes(), WORD_WEIGHT);
+
+            // Pad words so prefixes and suffixes hash differently from infixes.
+            let chars: Vec<char> = format!("<{word}>").chars().collect();
+            for n in self.char_ngrams.clone() {
+                for ngram in chars.windows(n) {
+                    let ngram: String = ngram.iter().collect();
+                    self.add_feature(&mut embedding, ngram.as_bytes(), CHAR_NGRAM_WEIGHT);
+                }
+            }
+        }
+
+        let norm = embedding.iter().map(|x| x * x).sum::<f32>().sqrt();
+        if norm > 0.0 {
+            embedding.iter_mut().for_each(|x| *x /= norm);
+        }
+        embedding
+    }
+
+    fn add_feature(&self, embedding: &mut [f32], feature: &[u8], weight: f32) {
+        let hash = fnv1a(feature);
+        let bucket = (hash % embedding.len() as u64) as usize;
+        let sign = if hash >> 63 == 0 { 1.0 } else { -1.0 };
+        embedding[bucket] += sign * weight;
+    }
+}
+
+/// 64-bit FNV-1a, stable across platforms and Rust versions.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[async_trait::async_trait]
+impl EmbeddingModel for HashingEmbedder {
+    fn model_info(&self) -> &ModelInfo {
+        &self.model
+    }
+
+    async fn embed(&self, input: &str) -> Result<Vec<f32>, EmbeddingError> {
+        Ok(self.embed_sync(input))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dot(a: &[f32], b: &[f32]) -> f32 {
+        a.iter().zip(b).map(|(x, y)| x * y).sum()
+    }
+
+    #[test]
+    fn test_embedding_is_deterministic_and_normalized() {
+        let embedder = HashingEmbedder::new(64);
+        let embedding = embedder.embed_sync("Go hiking in the Alps");
+
+        assert_eq!(embedding.len(), 64);
+        assert_eq!(embedding, embedder.embed_sync("go HIKING in the alps!"));
+        assert!((dot(&embedding, &embedding) - 1.0).abs() < 1e-5);
+        assert!(embedder.embed_sync("").iter().all(|x| *x == 0.0));
+    }
+
+    #[test]
+    fn test_lexical_similarity() {
+        let embedder = HashingEmbedder::new(DEFAULT_DIMENSION);
+        let query = embedder.embed_sync("mountain hiking");
+        let related = embedder.embed_sync("a weekend of hiking in the mountains");
+        let unrelated = embedder.embed_sync("bake sourdough bread");
+
+        assert!(dot(&query, &related) > dot(&query, &unrelated));
+    }
+}
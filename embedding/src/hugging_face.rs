@@ -1,37 +1,80 @@
-use super::{EmbeddingError, EmbeddingModel};
-use eyre::{WrapErr, eyre};
+use super::{
+    Distance, EmbeddingError, EmbeddingModel, InstructionPrefixes, ModelInfo,
+    credentials::{ExposeSecret, SecretString},
+};
+use eyre::eyre;
 use reqwest::Client;
 
+pub const DEFAULT_HUB_ENDPOINT: &str = "https://huggingface.co";
+
 pub struct HuggingFace {
-    api_key: String,
+    api_key: SecretString,
     endpoint: String,
+    hub_endpoint: String,
+    model: ModelInfo,
+    prefixes: InstructionPrefixes,
     client: Client,
 }
 
+/// Extract the model id from an inference endpoint such as
+/// `https://router.huggingface.co/hf-inference/models/BAAI/bge-base-en-v1.5/pipeline/feature-extraction`.
+///
+/// Falls back to the endpoint itself for URLs that don't follow that layout.
+fn model_id_from_endpoint(endpoint: &str) -> String {
+    endpoint
+        .split_once("/models/")
+        .map(|(_, rest)| rest.split("/pipeline/").next().unwrap_or(rest))
+        .map(|id| id.trim_end_matches('/'))
+        .filter(|id| !id.is_empty())
+        .unwrap_or(endpoint)
+        .to_string()
+}
+
 impl HuggingFace {
-    pub async fn new(api_key: String, endpoint: String) -> eyre::Result<Self> {
-        let client = Client::new();
-        client
-            .get("https://huggingface.co/api/whoami-v2")
-            .header("Authorization", format!("Bearer {}", api_key))
-            .send()
-            .await
-            .wrap_err("Failed to validate api key")?;
+    pub async fn new(
+        api_key: SecretString,
+        endpoint: String,
+        dimension: usize,
+    ) -> eyre::Result<Self> {
         Ok(Self {
             api_key,
+            model: ModelInfo::new(model_id_from_endpoint(&endpoint), dimension),
             endpoint,
-            client,
+            hub_endpoint: DEFAULT_HUB_ENDPOINT.to_string(),
+            prefixes: InstructionPrefixes::default(),
+            client: Client::new(),
         })
     }
+
+    /// Hub the API key is validated against, for private Hugging Face deployments.
+    pub fn with_hub_endpoint(mut self, hub_endpoint: String) -> Self {
+        self.hub_endpoint = hub_endpoint;
+        self
+    }
+
+    pub fn with_distance(mut self, distance: Distance) -> Self {
+        self.model.distance = distance;
+        self
+    }
+
+    pub fn with_prefixes(mut self, prefixes: InstructionPrefixes) -> Self {
+        self.prefixes = prefixes;
+        self
+    }
 }
 
 #[async_trait::async_trait]
 impl EmbeddingModel for HuggingFace {
+    fn model_info(&self) -> &ModelInfo {
+        &self.model
+    }
+
+    #[tracing::instrument(level = "debug", name = "hugging_face", skip_all, fields(model = %self.model.id))]
     async fn embed(&self, input: &str) -> Result<Vec<f32>, EmbeddingError> {
         let response = self
             .client
             .post(&self.endpoint)
-            .header("Authorization", format!("Bearer {}", self.api_key))
+            .bearer_auth(self.api_key.expose_secret())
             .json(&serde_json::json!({
                 "inputs": input,
             }))
@@ -45,4 +88,75 @@ impl EmbeddingModel for HuggingFace {
             .map_err(|e| EmbeddingError::RequestError(eyre!("Failed to parse response: {}", e)))?;
         Ok(body)
     }
+
+    async fn embed_query(&self, input: &str) -> Result<Vec<f32>, EmbeddingError> {
+        self.embed(&self.prefixes.query(input)).await
+    }
+
+    async fn embed_document(&self, input: &str) -> Result<Vec<f32>, EmbeddingError> {
+        self.embed(&self.prefixes.document(input)).await
+    }
+
+    async fn embed_documents(&self, inputs: &[&str]) -> Result<Vec<Vec<f32>>, EmbeddingError> {
+        let inputs = self.prefixes.documents(inputs);
+        self.embed_batch(&inputs.iter().map(AsRef::as_ref).collect::<Vec<_>>())
+            .await
+    }
+
+    async fn validate(&self) -> Result<(), EmbeddingError> {
+        self.client
+            .get(format!("{}/api/whoami-v2", self.hub_endpoint))
+            .bearer_auth(self.api_key.expose_secret())
+            .send()
+            .await
+            .map_err(|e| EmbeddingError::RequestError(eyre!("Failed to send request: {}", e)))?
+            .error_for_status()
+            .map_err(|e| EmbeddingError::RequestError(eyre!("API key was rejected: {}", e)))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_validate_checks_the_api_key() {
+        let mut server = mockito::Server::new_async().await;
+        let valid = server
+            .mock("GET", "/api/whoami-v2")
+            .match_header("authorization", "Bearer hf_valid")
+            .with_body(r#"{"name": "me"}"#)
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/api/whoami-v2")
+            .match_header("authorization", "Bearer hf_invalid")
+            .with_status(401)
+            .create_async()
+            .await;
+
+        for (api_key, is_valid) in [("hf_valid", true), ("hf_invalid", false)] {
+            let model = HuggingFace::new(api_key.into(), server.url(), 768)
+                .await
+                .unwrap()
+                .with_hub_endpoint(server.url());
+            assert_eq!(model.validate().await.is_ok(), is_valid);
+        }
+        valid.assert_async().await;
+    }
+
+    #[test]
+    fn test_model_id_from_endpoint() {
+        assert_eq!(
+            model_id_from_endpoint(
+                "https://router.huggingface.co/hf-inference/models/BAAI/bge-base-en-v1.5/pipeline/feature-extraction"
+            ),
+            "BAAI/bge-base-en-v1.5"
+        );
+        assert_eq!(
+            model_id_from_endpoint("https://example.com/embed"),
+            "https://example.com/embed"
+        );
+    }
 }
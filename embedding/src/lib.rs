@@ -1,5 +1,14 @@
+use std::{borrow::Cow, fmt, str::FromStr, sync::Arc};
+
+pub mod credentials;
+pub mod fallback;
+pub mod hashing;
 pub mod hugging_face;
+#[cfg(feature = "local")]
+pub mod local;
+pub mod ollama;
 pub mod self_hosted;
+pub mod tei;
 
 #[derive(Debug, thiserror::Error)]
 pub enum EmbeddingError {
@@ -9,7 +18,240 @@ pub enum EmbeddingError {
 
 type Result<T> = std::result::Result<T, EmbeddingError>;
 
+/// Input embedded by [`EmbeddingModel::health_check`].
+const HEALTH_CHECK_PROBE: &str = "health check";
+
+/// Distance function the embeddings of a model are meant to be compared with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Distance {
+    #[default]
+    Cosine,
+    Dot,
+    Euclid,
+    Manhattan,
+}
+
+impl fmt::Display for Distance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Distance::Cosine => "cosine",
+            Distance::Dot => "dot",
+            Distance::Euclid => "euclid",
+            Distance::Manhattan => "manhattan",
+        })
+    }
+}
+
+impl FromStr for Distance {
+    type Err = eyre::Error;
+
+    fn from_str(s: &str) -> eyre::Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "cosine" => Ok(Distance::Cosine),
+            "dot" => Ok(Distance::Dot),
+            "euclid" | "euclidean" => Ok(Distance::Euclid),
+            "manhattan" => Ok(Distance::Manhattan),
+            other => Err(eyre::eyre!("Unknown distance: {other}")),
+        }
+    }
+}
+
+/// Identity of the model behind an [`EmbeddingModel`].
+///
+/// Embeddings are only comparable when they were produced by the same model,
+/// so storage uses this to lay out collections and to reject mismatched writes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModelInfo {
+    pub id: String,
+    pub dimension: usize,
+    pub distance: Distance,
+}
+
+impl ModelInfo {
+    pub fn new(id: impl Into<String>, dimension: usize) -> Self {
+        Self {
+            id: id.into(),
+            dimension,
+            distance: Distance::default(),
+        }
+    }
+
+    pub fn with_distance(mut self, distance: Distance) -> Self {
+        self.distance = distance;
+        self
+    }
+}
+
+impl fmt::Display for ModelInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({}d, {})", self.id, self.dimension, self.distance)
+    }
+}
+
+/// Instruction prefixes prepended to inputs depending on their role.
+///
+/// BGE-style models expect queries to be prefixed with an instruction such as
+/// `"Represent this sentence for searching relevant passages: "` while stored
+/// documents are embedded as is.
+#[derive(Debug, Clone, Default)]
+pub struct InstructionPrefixes {
+    pub query: Option<String>,
+    pub document: Option<String>,
+}
+
+impl InstructionPrefixes {
+    pub fn query<'a>(&self, input: &'a str) -> Cow<'a, str> {
+        Self::apply(self.query.as_deref(), input)
+    }
+
+    pub fn document<'a>(&self, input: &'a str) -> Cow<'a, str> {
+        Self::apply(self.document.as_deref(), input)
+    }
+
+    pub fn documents<'a>(&self, inputs: &[&'a str]) -> Vec<Cow<'a, str>> {
+        inputs.iter().map(|input| self.document(input)).collect()
+    }
+
+    fn apply<'a>(prefix: Option<&str>, input: &'a str) -> Cow<'a, str> {
+        match prefix {
+            Some(prefix) if !prefix.is_empty() => Cow::Owned(format!("{prefix}{input}")),
+            _ => Cow::Borrowed(input),
+        }
+    }
+}
+
 #[async_trait::async_trait]
-pub trait EmbeddingModel {
+pub trait EmbeddingModel: Send + Sync {
+    /// Model identifier, output dimension and preferred distance.
+    fn model_info(&self) -> &ModelInfo;
+
     async fn embed(&self, input: &str) -> Result<Vec<f32>>;
+
+    /// Embed several inputs at once.
+    ///
+    /// Providers whose API accepts batched input should override this; the
+    /// default embeds the inputs one by one.
+    async fn embed_batch(&self, inputs: &[&str]) -> Result<Vec<Vec<f32>>> {
+        let mut embeddings = Vec::with_capacity(inputs.len());
+        for input in inputs {
+            embeddings.push(self.embed(input).await?);
+        }
+        Ok(embeddings)
+    }
+
+    /// Embed a search query.
+    async fn embed_query(&self, input: &str) -> Result<Vec<f32>> {
+        self.embed(input).await
+    }
+
+    /// Embed a document that is going to be stored and searched against.
+    async fn embed_document(&self, input: &str) -> Result<Vec<f32>> {
+        self.embed(input).await
+    }
+
+    /// Embed several documents at once, as `embed_document` would.
+    ///
+    /// Providers overriding `embed_document` should override this too, so
+    /// the documents are embedded in a single batch; the default embeds them
+    /// one by one.
+    async fn embed_documents(&self, inputs: &[&str]) -> Result<Vec<Vec<f32>>> {
+        let mut embeddings = Vec::with_capacity(inputs.len());
+        for input in inputs {
+            embeddings.push(self.embed_document(input).await?);
+        }
+        Ok(embeddings)
+    }
+
+    /// Check that the provider is usable without embedding anything, e.g.
+    /// that its credentials are accepted. Providers without such a check
+    /// report success.
+    async fn validate(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Validate the provider and embed a probe input, checking that the
+    /// embedding has the advertised dimension.
+    async fn health_check(&self) -> Result<()> {
+        self.validate().await?;
+        let embedding = self.embed_document(HEALTH_CHECK_PROBE).await?;
+        let expected = self.model_info().dimension;
+        if embedding.len() != expected {
+            return Err(EmbeddingError::RequestError(eyre::eyre!(
+                "Probe embedding has {} dimensions, expected {}",
+                embedding.len(),
+                expected
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Forward every method to the pointee, so provider-specific overrides are
+/// kept when a model is boxed or shared.
+macro_rules! forward_embedding_model {
+    ($pointer:ident) => {
+        #[async_trait::async_trait]
+        impl<M: EmbeddingModel + ?Sized> EmbeddingModel for $pointer<M> {
+            fn model_info(&self) -> &ModelInfo {
+                (**self).model_info()
+            }
+
+            async fn embed(&self, input: &str) -> Result<Vec<f32>> {
+                (**self).embed(input).await
+            }
+
+            async fn embed_batch(&self, inputs: &[&str]) -> Result<Vec<Vec<f32>>> {
+                (**self).embed_batch(inputs).await
+            }
+
+            async fn embed_query(&self, input: &str) -> Result<Vec<f32>> {
+                (**self).embed_query(input).await
+            }
+
+            async fn embed_document(&self, input: &str) -> Result<Vec<f32>> {
+                (**self).embed_document(input).await
+            }
+
+            async fn embed_documents(&self, inputs: &[&str]) -> Result<Vec<Vec<f32>>> {
+                (**self).embed_documents(inputs).await
+            }
+
+            async fn validate(&self) -> Result<()> {
+                (**self).validate().await
+            }
+
+            async fn health_check(&self) -> Result<()> {
+                (**self).health_check().await
+            }
+        }
+    };
+}
+
+forward_embedding_model!(Box);
+forward_embedding_model!(Arc);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hashing::HashingEmbedder;
+
+    fn assert_send_sync<T: Send + Sync + 'static>() {}
+
+    #[test]
+    fn test_pointers_are_embedding_models() {
+        assert_send_sync::<Box<dyn EmbeddingModel>>();
+        assert_send_sync::<Arc<dyn EmbeddingModel>>();
+    }
+
+    #[tokio::test]
+    async fn test_pointers_forward_overrides() {
+        let model = HashingEmbedder::new(32);
+        let expected = model.embed_sync("idea");
+
+        let boxed: Box<dyn EmbeddingModel> = Box::new(HashingEmbedder::new(32));
+        let shared: Arc<dyn EmbeddingModel> = Arc::from(boxed);
+        assert_eq!(shared.model_info(), model.model_info());
+        assert_eq!(shared.embed_document("idea").await.unwrap(), expected);
+        assert!(Box::new(shared).health_check().await.is_ok());
+    }
 }
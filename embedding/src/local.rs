@@ -0,0 +1,190 @@
+use super::{Distance, EmbeddingError, EmbeddingModel, InstructionPrefixes, ModelInfo};
+use candle_core::{DType, Device, Tensor};
+use candle_nn::VarBuilder;
+use candle_transformers::models::bert::{BertModel, Config, DTYPE};
+use eyre::{WrapErr, eyre};
+use std::{path::Path, sync::Arc};
+use tokenizers::{PaddingParams, Tokenizer, TruncationParams};
+
+const CONFIG_FILE: &str = "config.json";
+const TOKENIZER_FILE: &str = "tokenizer.json";
+const WEIGHTS_FILE: &str = "model.safetensors";
+
+struct Encoder {
+    model: BertModel,
+    tokenizer: Tokenizer,
+    device: Device,
+}
+
+/// Sentence-transformer model running on the CPU, without any network calls.
+///
+/// The model directory is expected to contain `config.json`, `tokenizer.json`
+/// and `model.safetensors`, as found in a sentence-transformers checkout of a
+/// BERT-family model (e.g. `BAAI/bge-small-en-v1.5`). Token embeddings are
+/// mean pooled over the attention mask.
+pub struct LocalModel {
+    encoder: Arc<Encoder>,
+    model: ModelInfo,
+    normalize: bool,
+    prefixes: InstructionPrefixes,
+}
+
+impl LocalModel {
+    pub async fn new(model_dir: &Path) -> eyre::Result<Self> {
+        let config: Config = serde_json::from_slice(
+            &std::fs::read(model_dir.join(CONFIG_FILE))
+                .wrap_err_with(|| format!("Failed to read {CONFIG_FILE}"))?,
+        )
+        .wrap_err_with(|| format!("Failed to parse {CONFIG_FILE}"))?;
+
+        let mut tokenizer = Tokenizer::from_file(model_dir.join(TOKENIZER_FILE))
+            .map_err(|e| eyre!("Failed to load {TOKENIZER_FILE}: {e}"))?;
+        tokenizer
+            .with_padding(Some(PaddingParams::default()))
+            .with_truncation(Some(TruncationParams {
+                max_length: config.max_position_embeddings,
+                ..Default::default()
+            }))
+            .map_err(|e| eyre!("Failed to configure tokenizer: {e}"))?;
+
+        let device = Device::Cpu;
+        // SAFETY: the weights file is memory mapped read-only and is not
+        // expected to be modified while the model is loaded.
+        let vb = unsafe {
+            VarBuilder::from_mmaped_safetensors(&[model_dir.join(WEIGHTS_FILE)], DTYPE, &device)
+        }
+        .wrap_err_with(|| format!("Failed to load {WEIGHTS_FILE}"))?;
+        let model = BertModel::load(vb, &config).wrap_err("Failed to load model weights")?;
+
+        let model_id = model_dir
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| model_dir.display().to_string());
+
+        Ok(Self {
+            encoder: Arc::new(Encoder {
+                model,
+                tokenizer,
+                device,
+            }),
+            model: ModelInfo::new(model_id, config.hidden_size),
+            normalize: true,
+            prefixes: InstructionPrefixes::default(),
+        })
+    }
+
+    pub fn with_normalize(mut self, normalize: bool) -> Self {
+        self.normalize = normalize;
+        self
+    }
+
+    pub fn with_distance(mut self, distance: Distance) -> Self {
+        self.model.distance = distance;
+        self
+    }
+
+    pub fn with_prefixes(mut self, prefixes: InstructionPrefixes) -> Self {
+        self.prefixes = prefixes;
+        self
+    }
+}
+
+impl Encoder {
+    fn encode(&self, inputs: Vec<String>, normalize: bool) -> eyre::Result<Vec<Vec<f32>>> {
+        let encodings = self
+            .tokenizer
+            .encode_batch(inputs, true)
+            .map_err(|e| eyre!("Failed to tokenize input: {e}"))?;
+
+        let input_ids = encodings
+            .iter()
+            .map(|encoding| Tensor::new(encoding.get_ids(), &self.device))
+            .collect::<candle_core::Result<Vec<_>>>()?;
+        let attention_mask = encodings
+            .iter()
+            .map(|encoding| Tensor::new(encoding.get_attention_mask(), &self.device))
+            .collect::<candle_core::Result<Vec<_>>>()?;
+        let input_ids = Tensor::stack(&input_ids, 0)?;
+        let attention_mask = Tensor::stack(&attention_mask, 0)?;
+        let token_type_ids = input_ids.zeros_like()?;
+
+        let hidden = self
+            .model
+            .forward(&input_ids, &token_type_ids, Some(&attention_mask))?;
+        let mut pooled = mean_pool(&hidden, &attention_mask)?;
+        if normalize {
+            pooled = normalize_l2(&pooled)?;
+        }
+        Ok(pooled.to_vec2::<f32>()?)
+    }
+}
+
+/// Average the token embeddings `[batch, tokens, hidden]`, ignoring padding.
+fn mean_pool(hidden: &Tensor, attention_mask: &Tensor) -> candle_core::Result<Tensor> {
+    let mask = attention_mask.to_dtype(DType::F32)?.unsqueeze(2)?;
+    let summed = hidden.broadcast_mul(&mask)?.sum(1)?;
+    let counts = mask.sum(1)?.clamp(1e-9, f32::MAX)?;
+    summed.broadcast_div(&counts)
+}
+
+fn normalize_l2(embeddings: &Tensor) -> candle_core::Result<Tensor> {
+    let norms = embeddings.sqr()?.sum_keepdim(1)?.sqrt()?;
+    embeddings.broadcast_div(&norms.clamp(1e-12, f32::MAX)?)
+}
+
+#[async_trait::async_trait]
+impl EmbeddingModel for LocalModel {
+    fn model_info(&self) -> &ModelInfo {
+        &self.model
+    }
+
+    async fn embed(&self, input: &str) -> Result<Vec<f32>, EmbeddingError> {
+        self.embed_batch(&[input])
+            .await?
+            .pop()
+            .ok_or_else(|| EmbeddingError::RequestError(eyre!("Model returned no embeddings")))
+    }
+
+    async fn embed_batch(&self, inputs: &[&str]) -> Result<Vec<Vec<f32>>, EmbeddingError> {
+        let encoder = Arc::clone(&self.encoder);
+        let inputs = inputs.iter().map(|input| input.to_string()).collect();
+        let normalize = self.normalize;
+
+        tokio::task::spawn_blocking(move || encoder.encode(inputs, normalize))
+            .await
+            .map_err(|e| EmbeddingError::RequestError(eyre!("Inference task failed: {}", e)))?
+            .map_err(EmbeddingError::RequestError)
+    }
+
+    async fn embed_query(&self, input: &str) -> Result<Vec<f32>, EmbeddingError> {
+        self.embed(&self.prefixes.query(input)).await
+    }
+
+    async fn embed_document(&self, input: &str) -> Result<Vec<f32>, EmbeddingError> {
+        self.embed(&self.prefixes.document(input)).await
+    }
+
+    async fn embed_documents(&self, inputs: &[&str]) -> Result<Vec<Vec<f32>>, EmbeddingError> {
+        let inputs = self.prefixes.documents(inputs);
+        self.embed_batch(&inputs.iter().map(AsRef::as_ref).collect::<Vec<_>>())
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mean_pool_ignores_padding() {
+        let device = Device::Cpu;
+        let hidden = Tensor::new(&[[[1.0f32, 2.0], [3.0, 4.0], [100.0, 100.0]]], &device).unwrap();
+        let mask = Tensor::new(&[[1u32, 1, 0]], &device).unwrap();
+
+        let pooled = mean_pool(&hidden, &mask).unwrap();
+        assert_eq!(pooled.to_vec2::<f32>().unwrap(), vec![vec![2.0, 3.0]]);
+
+        let normalized = normalize_l2(&Tensor::new(&[[3.0f32, 4.0]], &device).unwrap()).unwrap();
+        assert_eq!(normalized.to_vec2::<f32>().unwrap(), vec![vec![0.6, 0.8]]);
+    }
+}
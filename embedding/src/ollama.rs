@@ -0,0 +1,278 @@
+use super::{Distance, EmbeddingError, EmbeddingModel, InstructionPrefixes, ModelInfo};
+use eyre::eyre;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+pub const DEFAULT_ENDPOINT: &str = "http://localhost:11434";
+
+/// How long Ollama keeps the model loaded after a request.
+///
+/// Ollama accepts either a duration string (`"5m"`, `"1h"`) or a number of
+/// seconds, where a negative value keeps the model loaded indefinitely and
+/// `0` unloads it right after the request.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum KeepAlive {
+    Duration(String),
+    Seconds(i64),
+}
+
+impl FromStr for KeepAlive {
+    type Err = std::convert::Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.parse::<i64>() {
+            Ok(seconds) => KeepAlive::Seconds(seconds),
+            Err(_) => KeepAlive::Duration(s.to_string()),
+        })
+    }
+}
+
+#[derive(Serialize)]
+struct EmbedRequest<'a> {
+    model: &'a str,
+    input: &'a [&'a str],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    keep_alive: Option<&'a KeepAlive>,
+}
+
+#[derive(Deserialize)]
+struct EmbedResponse {
+    embeddings: Vec<Vec<f32>>,
+}
+
+pub struct Ollama {
+    endpoint: String,
+    model: ModelInfo,
+    keep_alive: Option<KeepAlive>,
+    prefixes: InstructionPrefixes,
+    client: Client,
+}
+
+impl Ollama {
+    pub async fn new(endpoint: String, model: String, dimension: usize) -> eyre::Result<Self> {
+        Ok(Self {
+            endpoint,
+            model: ModelInfo::new(model, dimension),
+            keep_alive: None,
+            prefixes: InstructionPrefixes::default(),
+            client: Client::new(),
+        })
+    }
+
+    pub fn with_keep_alive(mut self, keep_alive: KeepAlive) -> Self {
+        self.keep_alive = Some(keep_alive);
+        self
+    }
+
+    pub fn with_distance(mut self, distance: Distance) -> Self {
+        self.model.distance = distance;
+        self
+    }
+
+    pub fn with_prefixes(mut self, prefixes: InstructionPrefixes) -> Self {
+        self.prefixes = prefixes;
+        self
+    }
+
+    fn embed_url(&self) -> String {
+        format!("{}/api/embed", self.endpoint.trim_end_matches('/'))
+    }
+}
+
+#[async_trait::async_trait]
+impl EmbeddingModel for Ollama {
+    fn model_info(&self) -> &ModelInfo {
+        &self.model
+    }
+
+    async fn embed(&self, input: &str) -> Result<Vec<f32>, EmbeddingError> {
+        self.embed_batch(&[input])
+            .await?
+            .pop()
+            .ok_or_else(|| EmbeddingError::RequestError(eyre!("Ollama returned no embeddings")))
+    }
+
+    #[tracing::instrument(level = "debug", name = "ollama", skip_all, fields(model = %self.model.id, inputs = inputs.len()))]
+    async fn embed_batch(&self, inputs: &[&str]) -> Result<Vec<Vec<f32>>, EmbeddingError> {
+        let response = self
+            .client
+            .post(self.embed_url())
+            .json(&EmbedRequest {
+                model: &self.model.id,
+                input: inputs,
+                keep_alive: self.keep_alive.as_ref(),
+            })
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| EmbeddingError::RequestError(eyre!("Failed to send request: {}", e)))?;
+
+        let body = response
+            .json::<EmbedResponse>()
+            .await
+            .map_err(|e| EmbeddingError::RequestError(eyre!("Failed to parse response: {}", e)))?;
+
+        if body.embeddings.len() != inputs.len() {
+            return Err(EmbeddingError::RequestError(eyre!(
+                "Expected {} embeddings, got {}",
+                inputs.len(),
+                body.embeddings.len()
+            )));
+        }
+        Ok(body.embeddings)
+    }
+
+    async fn embed_query(&self, input: &str) -> Result<Vec<f32>, EmbeddingError> {
+        self.embed(&self.prefixes.query(input)).await
+    }
+
+    async fn embed_document(&self, input: &str) -> Result<Vec<f32>, EmbeddingError> {
+        self.embed(&self.prefixes.document(input)).await
+    }
+
+    async fn embed_documents(&self, inputs: &[&str]) -> Result<Vec<Vec<f32>>, EmbeddingError> {
+        let inputs = self.prefixes.documents(inputs);
+        self.embed_batch(&inputs.iter().map(AsRef::as_ref).collect::<Vec<_>>())
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mockito::Matcher;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_embed_single_input() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/api/embed")
+            .match_body(Matcher::Json(json!({
+                "model": "nomic-embed-text",
+                "input": ["hello"],
+            })))
+            .with_body(
+                json!({ "model": "nomic-embed-text", "embeddings": [[0.1, 0.2, 0.3]] }).to_string(),
+            )
+            .create_async()
+            .await;
+
+        let ollama = Ollama::new(server.url(), "nomic-embed-text".to_string(), 3)
+            .await
+            .unwrap();
+        let embedding = ollama.embed("hello").await.unwrap();
+
+        assert_eq!(embedding, vec![0.1, 0.2, 0.3]);
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_embed_batch_with_keep_alive() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/api/embed")
+            .match_body(Matcher::Json(json!({
+                "model": "nomic-embed-text",
+                "input": ["first", "second"],
+                "keep_alive": -1,
+            })))
+            .with_body(json!({ "embeddings": [[1.0, 0.0], [0.0, 1.0]] }).to_string())
+            .create_async()
+            .await;
+
+        let ollama = Ollama::new(server.url(), "nomic-embed-text".to_string(), 3)
+            .await
+            .unwrap()
+            .with_keep_alive("-1".parse().unwrap());
+        let embeddings = ollama.embed_batch(&["first", "second"]).await.unwrap();
+
+        assert_eq!(embeddings, vec![vec![1.0, 0.0], vec![0.0, 1.0]]);
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_embed_documents_in_one_request() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/api/embed")
+            .match_body(Matcher::Json(json!({
+                "model": "nomic-embed-text",
+                "input": ["search_document: first", "search_document: second"],
+            })))
+            .with_body(json!({ "embeddings": [[1.0, 0.0], [0.0, 1.0]] }).to_string())
+            .expect(1)
+            .create_async()
+            .await;
+
+        let ollama = Ollama::new(server.url(), "nomic-embed-text".to_string(), 2)
+            .await
+            .unwrap()
+            .with_prefixes(InstructionPrefixes {
+                query: None,
+                document: Some("search_document: ".to_string()),
+            });
+        let embeddings = ollama.embed_documents(&["first", "second"]).await.unwrap();
+
+        assert_eq!(embeddings, vec![vec![1.0, 0.0], vec![0.0, 1.0]]);
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_embed_error_status() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", "/api/embed")
+            .with_status(404)
+            .with_body(json!({ "error": "model \"missing\" not found" }).to_string())
+            .create_async()
+            .await;
+
+        let ollama = Ollama::new(server.url(), "missing".to_string(), 3)
+            .await
+            .unwrap();
+
+        assert!(ollama.embed("hello").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_embed_query_and_document_prefixes() {
+        let mut server = mockito::Server::new_async().await;
+        let query_mock = server
+            .mock("POST", "/api/embed")
+            .match_body(Matcher::PartialJson(json!({ "input": ["query: hiking"] })))
+            .with_body(json!({ "embeddings": [[1.0]] }).to_string())
+            .create_async()
+            .await;
+        let document_mock = server
+            .mock("POST", "/api/embed")
+            .match_body(Matcher::PartialJson(json!({ "input": ["hiking"] })))
+            .with_body(json!({ "embeddings": [[2.0]] }).to_string())
+            .create_async()
+            .await;
+
+        let ollama = Ollama::new(server.url(), "nomic-embed-text".to_string(), 3)
+            .await
+            .unwrap()
+            .with_prefixes(InstructionPrefixes {
+                query: Some("query: ".to_string()),
+                document: None,
+            });
+
+        assert_eq!(ollama.embed_query("hiking").await.unwrap(), vec![1.0]);
+        assert_eq!(ollama.embed_document("hiking").await.unwrap(), vec![2.0]);
+        query_mock.assert_async().await;
+        document_mock.assert_async().await;
+    }
+
+    #[test]
+    fn test_keep_alive_parsing() {
+        assert_eq!("0".parse::<KeepAlive>().unwrap(), KeepAlive::Seconds(0));
+        assert_eq!(
+            "10m".parse::<KeepAlive>().unwrap(),
+            KeepAlive::Duration("10m".to_string())
+        );
+    }
+}
@@ -1,22 +1,36 @@
-use super::{EmbeddingError, EmbeddingModel};
+use super::{EmbeddingError, EmbeddingModel, InstructionPrefixes, ModelInfo};
 use eyre::eyre;
 
 pub struct SelfHosted {
     endpoint: String,
+    model: ModelInfo,
+    prefixes: InstructionPrefixes,
     client: reqwest::Client,
 }
 
 impl SelfHosted {
-    pub async fn new(endpoint: String) -> eyre::Result<Self> {
+    pub async fn new(endpoint: String, model: ModelInfo) -> eyre::Result<Self> {
         Ok(Self {
             endpoint,
+            model,
+            prefixes: InstructionPrefixes::default(),
             client: reqwest::Client::new(),
         })
     }
+
+    pub fn with_prefixes(mut self, prefixes: InstructionPrefixes) -> Self {
+        self.prefixes = prefixes;
+        self
+    }
 }
 
 #[async_trait::async_trait]
 impl EmbeddingModel for SelfHosted {
+    fn model_info(&self) -> &ModelInfo {
+        &self.model
+    }
+
+    #[tracing::instrument(level = "debug", name = "self_hosted", skip_all, fields(model = %self.model.id))]
     async fn embed(&self, input: &str) -> Result<Vec<f32>, EmbeddingError> {
         let response = self
             .client
@@ -30,4 +44,18 @@ impl EmbeddingModel for SelfHosted {
             .await
             .map_err(|e| EmbeddingError::RequestError(eyre!("Failed to parse response: {}", e)))?)
     }
+
+    async fn embed_query(&self, input: &str) -> Result<Vec<f32>, EmbeddingError> {
+        self.embed(&self.prefixes.query(input)).await
+    }
+
+    async fn embed_document(&self, input: &str) -> Result<Vec<f32>, EmbeddingError> {
+        self.embed(&self.prefixes.document(input)).await
+    }
+
+    async fn embed_documents(&self, inputs: &[&str]) -> Result<Vec<Vec<f32>>, EmbeddingError> {
+        let inputs = self.prefixes.documents(inputs);
+        self.embed_batch(&inputs.iter().map(AsRef::as_ref).collect::<Vec<_>>())
+            .await
+    }
 }
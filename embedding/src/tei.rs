@@ -0,0 +1,404 @@
+use super::{Distance, EmbeddingError, EmbeddingModel, InstructionPrefixes, ModelInfo};
+use eyre::{WrapErr, eyre};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+
+/// Model information reported by the TEI `/info` route.
+#[derive(Debug, Clone, Deserialize)]
+pub struct TeiInfo {
+    pub model_id: String,
+    pub max_input_length: Option<usize>,
+    pub max_client_batch_size: Option<usize>,
+}
+
+#[derive(Serialize)]
+struct EmbedRequest<'a> {
+    inputs: &'a [&'a str],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    truncate: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    normalize: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    prompt_name: Option<&'a str>,
+}
+
+/// HuggingFace Text Embeddings Inference (TEI) provider.
+///
+/// Documents are embedded with `prompt_name` and queries with
+/// `query_prompt_name`, so models shipping with `query`/`passage` prompts in
+/// their sentence-transformers config can be used in both modes.
+pub struct TextEmbeddingsInference {
+    endpoint: String,
+    truncate: Option<bool>,
+    normalize: Option<bool>,
+    prompt_name: Option<String>,
+    query_prompt_name: Option<String>,
+    prefixes: InstructionPrefixes,
+    model: ModelInfo,
+    info: Option<TeiInfo>,
+    client: Client,
+}
+
+impl TextEmbeddingsInference {
+    pub async fn new(endpoint: String, model: ModelInfo) -> eyre::Result<Self> {
+        Ok(Self {
+            endpoint,
+            truncate: None,
+            normalize: None,
+            prompt_name: None,
+            query_prompt_name: None,
+            prefixes: InstructionPrefixes::default(),
+            model,
+            info: None,
+            client: Client::new(),
+        })
+    }
+
+    /// Create the provider and ask the server which model it serves.
+    ///
+    /// TEI does not report the output dimension, so a probe embedding is
+    /// requested to learn it. To probe with `truncate`, `normalize` or a
+    /// prompt name, create the provider with [`Self::new`], set them and call
+    /// [`Self::fetch_info`] instead.
+    pub async fn from_info(endpoint: String) -> eyre::Result<Self> {
+        let mut tei = Self::new(endpoint.clone(), ModelInfo::new(endpoint, 0)).await?;
+        tei.fetch_info().await?;
+        Ok(tei)
+    }
+
+    pub fn with_truncate(mut self, truncate: bool) -> Self {
+        self.truncate = Some(truncate);
+        self
+    }
+
+    pub fn with_normalize(mut self, normalize: bool) -> Self {
+        self.normalize = Some(normalize);
+        self
+    }
+
+    pub fn with_prompt_name(mut self, prompt_name: String) -> Self {
+        self.prompt_name = Some(prompt_name);
+        self
+    }
+
+    pub fn with_query_prompt_name(mut self, prompt_name: String) -> Self {
+        self.query_prompt_name = Some(prompt_name);
+        self
+    }
+
+    pub fn with_distance(mut self, distance: Distance) -> Self {
+        self.model.distance = distance;
+        self
+    }
+
+    pub fn with_prefixes(mut self, prefixes: InstructionPrefixes) -> Self {
+        self.prefixes = prefixes;
+        self
+    }
+
+    pub fn info(&self) -> Option<&TeiInfo> {
+        self.info.as_ref()
+    }
+
+    /// Query the `/info` route and probe the output dimension, replacing the
+    /// model id and dimension the provider was created with.
+    ///
+    /// The probe is embedded like a stored idea, with the options set so far.
+    pub async fn fetch_info(&mut self) -> eyre::Result<&TeiInfo> {
+        let info = self.request_info().await?;
+        let probe = self
+            .embed_with_prompt(&["dimension probe"], self.prompt_name.as_deref())
+            .await
+            .wrap_err("Failed to probe TEI embedding dimension")?;
+
+        self.model.id = info.model_id.clone();
+        self.model.dimension = probe.first().map(Vec::len).unwrap_or_default();
+        Ok(self.info.insert(info))
+    }
+
+    async fn request_info(&self) -> eyre::Result<TeiInfo> {
+        self.client
+            .get(self.url("info"))
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .wrap_err("Failed to fetch TEI model info")?
+            .json::<TeiInfo>()
+            .await
+            .wrap_err("Failed to parse TEI model info")
+    }
+
+    #[tracing::instrument(level = "debug", name = "tei", skip_all, fields(model = %self.model.id, inputs = inputs.len()))]
+    pub async fn embed_with_prompt(
+        &self,
+        inputs: &[&str],
+        prompt_name: Option<&str>,
+    ) -> Result<Vec<Vec<f32>>, EmbeddingError> {
+        let batch_size = self
+            .info
+            .as_ref()
+            .and_then(|info| info.max_client_batch_size)
+            .unwrap_or(inputs.len())
+            .max(1);
+
+        let mut embeddings = Vec::with_capacity(inputs.len());
+        for batch in inputs.chunks(batch_size) {
+            let response = self
+                .client
+                .post(self.url("embed"))
+                .json(&EmbedRequest {
+                    inputs: batch,
+                    truncate: self.truncate,
+                    normalize: self.normalize,
+                    prompt_name,
+                })
+                .send()
+                .await
+                .and_then(|r| r.error_for_status())
+                .map_err(|e| {
+                    EmbeddingError::RequestError(eyre!("Failed to send request: {}", e))
+                })?;
+
+            let body = response.json::<Vec<Vec<f32>>>().await.map_err(|e| {
+                EmbeddingError::RequestError(eyre!("Failed to parse response: {}", e))
+            })?;
+            if body.len() != batch.len() {
+                return Err(EmbeddingError::RequestError(eyre!(
+                    "Expected {} embeddings, got {}",
+                    batch.len(),
+                    body.len()
+                )));
+            }
+            embeddings.extend(body);
+        }
+        Ok(embeddings)
+    }
+
+    fn url(&self, route: &str) -> String {
+        format!("{}/{}", self.endpoint.trim_end_matches('/'), route)
+    }
+}
+
+#[async_trait::async_trait]
+impl EmbeddingModel for TextEmbeddingsInference {
+    fn model_info(&self) -> &ModelInfo {
+        &self.model
+    }
+
+    async fn embed(&self, input: &str) -> Result<Vec<f32>, EmbeddingError> {
+        self.embed_batch(&[input])
+            .await?
+            .pop()
+            .ok_or_else(|| EmbeddingError::RequestError(eyre!("TEI returned no embeddings")))
+    }
+
+    async fn embed_batch(&self, inputs: &[&str]) -> Result<Vec<Vec<f32>>, EmbeddingError> {
+        self.embed_with_prompt(inputs, self.prompt_name.as_deref())
+            .await
+    }
+
+    async fn embed_query(&self, input: &str) -> Result<Vec<f32>, EmbeddingError> {
+        self.embed_with_prompt(
+            &[&self.prefixes.query(input)],
+            self.query_prompt_name.as_deref(),
+        )
+        .await?
+        .pop()
+        .ok_or_else(|| EmbeddingError::RequestError(eyre!("TEI returned no embeddings")))
+    }
+
+    async fn embed_document(&self, input: &str) -> Result<Vec<f32>, EmbeddingError> {
+        self.embed(&self.prefixes.document(input)).await
+    }
+
+    async fn embed_documents(&self, inputs: &[&str]) -> Result<Vec<Vec<f32>>, EmbeddingError> {
+        let inputs = self.prefixes.documents(inputs);
+        self.embed_batch(&inputs.iter().map(AsRef::as_ref).collect::<Vec<_>>())
+            .await
+    }
+
+    /// Check that the server serves the configured model.
+    async fn validate(&self) -> Result<(), EmbeddingError> {
+        let info = self
+            .request_info()
+            .await
+            .map_err(EmbeddingError::RequestError)?;
+        if info.model_id != self.model.id {
+            return Err(EmbeddingError::RequestError(eyre!(
+                "TEI serves {}, not {}",
+                info.model_id,
+                self.model.id
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mockito::Matcher;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_embed_with_options() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/embed")
+            .match_body(Matcher::Json(json!({
+                "inputs": ["a document"],
+                "truncate": true,
+                "normalize": false,
+                "prompt_name": "passage",
+            })))
+            .with_body(json!([[0.5, 0.5]]).to_string())
+            .create_async()
+            .await;
+
+        let tei = TextEmbeddingsInference::new(server.url(), ModelInfo::new("test", 2))
+            .await
+            .unwrap()
+            .with_truncate(true)
+            .with_normalize(false)
+            .with_prompt_name("passage".to_string())
+            .with_query_prompt_name("query".to_string());
+
+        assert_eq!(tei.embed("a document").await.unwrap(), vec![0.5, 0.5]);
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_embed_query_uses_query_prompt() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/embed")
+            .match_body(Matcher::Json(json!({
+                "inputs": ["a question"],
+                "prompt_name": "query",
+            })))
+            .with_body(json!([[1.0, 0.0]]).to_string())
+            .create_async()
+            .await;
+
+        let tei = TextEmbeddingsInference::new(server.url(), ModelInfo::new("test", 2))
+            .await
+            .unwrap()
+            .with_query_prompt_name("query".to_string());
+
+        assert_eq!(tei.embed_query("a question").await.unwrap(), vec![1.0, 0.0]);
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_from_info() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/info")
+            .with_body(
+                json!({
+                    "model_id": "BAAI/bge-small-en-v1.5",
+                    "max_input_length": 512,
+                    "max_client_batch_size": 2,
+                })
+                .to_string(),
+            )
+            .create_async()
+            .await;
+        server
+            .mock("POST", "/embed")
+            .with_body(json!([[0.1, 0.2, 0.3]]).to_string())
+            .create_async()
+            .await;
+
+        let tei = TextEmbeddingsInference::from_info(server.url())
+            .await
+            .unwrap();
+
+        assert_eq!(tei.info().unwrap().model_id, "BAAI/bge-small-en-v1.5");
+        assert_eq!(tei.model_info().id, "BAAI/bge-small-en-v1.5");
+        assert_eq!(tei.model_info().dimension, 3);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_info_probes_with_options() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/info")
+            .with_body(json!({ "model_id": "intfloat/e5-small-v2" }).to_string())
+            .create_async()
+            .await;
+        let probe = server
+            .mock("POST", "/embed")
+            .match_body(Matcher::Json(json!({
+                "inputs": ["dimension probe"],
+                "truncate": true,
+                "prompt_name": "passage",
+            })))
+            .with_body(json!([[0.1, 0.2]]).to_string())
+            .create_async()
+            .await;
+
+        let mut tei = TextEmbeddingsInference::new(server.url(), ModelInfo::new("", 0))
+            .await
+            .unwrap()
+            .with_truncate(true)
+            .with_prompt_name("passage".to_string());
+        tei.fetch_info().await.unwrap();
+
+        assert_eq!(tei.model_info().id, "intfloat/e5-small-v2");
+        assert_eq!(tei.model_info().dimension, 2);
+        probe.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_validate_checks_served_model() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/info")
+            .with_body(json!({ "model_id": "BAAI/bge-small-en-v1.5" }).to_string())
+            .create_async()
+            .await;
+
+        let tei = |id| TextEmbeddingsInference::new(server.url(), ModelInfo::new(id, 384));
+        assert!(
+            tei("BAAI/bge-small-en-v1.5")
+                .await
+                .unwrap()
+                .validate()
+                .await
+                .is_ok()
+        );
+        assert!(
+            tei("BAAI/bge-base-en-v1.5")
+                .await
+                .unwrap()
+                .validate()
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_embed_batch_respects_client_batch_size() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/info")
+            .with_body(json!({ "model_id": "m", "max_client_batch_size": 1 }).to_string())
+            .create_async()
+            .await;
+        let mock = server
+            .mock("POST", "/embed")
+            .with_body(json!([[1.0]]).to_string())
+            .expect(3)
+            .create_async()
+            .await;
+
+        let tei = TextEmbeddingsInference::from_info(server.url())
+            .await
+            .unwrap();
+        let embeddings = tei.embed_batch(&["a", "b"]).await.unwrap();
+
+        assert_eq!(embeddings, vec![vec![1.0], vec![1.0]]);
+        mock.assert_async().await;
+    }
+}
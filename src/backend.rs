@@ -0,0 +1,132 @@
+use daemon::{
+    IdeaEntry,
+    client::{ClientError, DaemonClient},
+};
+use embedding::EmbeddingModel;
+use eyre::Result;
+use storage_client::{Idea, TopicStorage};
+
+use crate::{Cli, DaemonOptions, daemon_control::spawn_daemon};
+
+/// Where idea commands are executed
+///
+/// A running daemon keeps the embedding provider and the Qdrant connection
+/// warm, so it is preferred over opening the storage in this process.
+pub(crate) enum IdeaBackend {
+    Daemon(DaemonClient),
+    Direct(TopicStorage<Box<dyn EmbeddingModel>>),
+}
+
+impl IdeaBackend {
+    pub async fn open(cli: &Cli) -> Result<Self> {
+        if cli.no_daemon {
+            return Ok(Self::Direct(cli.storage().await?));
+        }
+        match DaemonClient::connect_to(cli.daemon_socket()).await {
+            Ok(client) if !cli.sets_storage() => return Ok(Self::Daemon(client)),
+            Ok(mut client) => {
+                // Opening the storage doesn't reach Qdrant, and it is what
+                // this command uses if the daemon serves something else.
+                let storage = cli.storage().await?;
+                match daemon_mismatch(&mut client, &storage).await {
+                    None => return Ok(Self::Daemon(client)),
+                    Some(mismatch) => {
+                        tracing::warn!(
+                            "Not using the daemon, {mismatch}; restart it with these settings or pass --no-daemon"
+                        );
+                        return Ok(Self::Direct(storage));
+                    }
+                }
+            }
+            Err(ClientError::Connect(_)) if cli.start_daemon => {
+                return Ok(Self::Daemon(
+                    spawn_daemon(cli, &DaemonOptions::default()).await?,
+                ));
+            }
+            Err(ClientError::Connect(_)) => {}
+            Err(e) => tracing::warn!("Not using the daemon: {e}"),
+        }
+        Ok(Self::Direct(cli.storage().await?))
+    }
+
+    /// Store an idea and return its id.
+    pub async fn create_idea(&mut self, topic: &str, content: &str) -> Result<u64> {
+        Ok(match self {
+            Self::Daemon(client) => client.create_idea(topic, content).await?,
+            Self::Direct(storage) => {
+                let idea = Idea::new(topic.to_string(), content.to_string(), None);
+                storage.create_topic(topic, idea).await?
+            }
+        })
+    }
+
+    pub async fn search(
+        &mut self,
+        topic: Option<&str>,
+        query: &str,
+        limit: u64,
+    ) -> Result<Vec<IdeaEntry>> {
+        Ok(match self {
+            Self::Daemon(client) => client.search(topic, query, limit).await?,
+            Self::Direct(storage) => storage
+                .search_topic(topic, query, limit)
+                .await?
+                .into_iter()
+                .filter_map(IdeaEntry::from_stored)
+                .collect(),
+        })
+    }
+
+    pub async fn list(&mut self, topic: &str, limit: u32) -> Result<Vec<IdeaEntry>> {
+        Ok(match self {
+            Self::Daemon(client) => client.list(topic, limit).await?,
+            Self::Direct(storage) => storage
+                .list_topic(topic, limit)
+                .await?
+                .into_iter()
+                .filter_map(IdeaEntry::from_stored)
+                .collect(),
+        })
+    }
+
+    pub async fn delete(&mut self, idea_id: u64) -> Result<()> {
+        match self {
+            Self::Daemon(client) => client.delete(idea_id).await?,
+            Self::Direct(storage) => storage.delete_idea(idea_id).await?,
+        }
+        Ok(())
+    }
+}
+
+/// How the daemon's storage differs from `storage`, if it does.
+async fn daemon_mismatch(
+    client: &mut DaemonClient,
+    storage: &TopicStorage<Box<dyn EmbeddingModel>>,
+) -> Option<String> {
+    let info = match client.info().await {
+        Ok(info) => info,
+        Err(e) => return Some(format!("whose settings can't be checked ({e})")),
+    };
+    let model = storage.model_info();
+    if info.provider != model.id || info.dimension != model.dimension as u64 {
+        return Some(format!(
+            "which embeds with {} ({}d) instead of {} ({}d)",
+            info.provider, info.dimension, model.id, model.dimension
+        ));
+    }
+    if info.collection != storage.collection_name() {
+        return Some(format!(
+            "which serves collection {} instead of {}",
+            info.collection,
+            storage.collection_name()
+        ));
+    }
+    let chunking = storage
+        .chunking()
+        .map(|chunking| (chunking.max_words as u64, chunking.overlap_words as u64));
+    let daemon_chunking = info.chunk_words.map(|words| (words, info.chunk_overlap));
+    if chunking != daemon_chunking {
+        return Some("which splits ideas into chunks differently".to_string());
+    }
+    None
+}
@@ -0,0 +1,230 @@
+use clap::Parser;
+use daemon::{
+    client::{ClientError, DaemonClient},
+    http::HttpGateway,
+    paths::DaemonPaths,
+    systemd,
+};
+use embedding::EmbeddingModel;
+use eyre::{Result, WrapErr, eyre};
+use nix::{
+    sys::signal::{Signal, kill},
+    unistd::Pid,
+};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    time::{Duration, Instant},
+};
+use storage_client::TopicStorage;
+
+use crate::{Cli, DaemonOptions};
+
+/// How long a freshly spawned daemon gets to open its socket.
+const DAEMON_START_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long a stopping daemon gets to exit; it drains requests for up to 10s first.
+const DAEMON_STOP_TIMEOUT: Duration = Duration::from_secs(15);
+const DAEMON_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Settings read from the daemon config file, written like the matching global
+/// arguments (e.g. `--provider "ollama --model nomic-embed-text"`).
+///
+/// They override the command line when the daemon starts and are read again
+/// on SIGHUP, so the daemon can switch providers without a restart.
+#[derive(Parser, Debug, Default)]
+#[command(no_binary_name = true)]
+struct DaemonConfig {
+    #[arg(long, short)]
+    /// Qdrant endpoint
+    qdrant_endpoint: Option<String>,
+
+    #[arg(long, short)]
+    /// Embedding provider, written like its `provider` subcommand
+    provider: Option<String>,
+}
+
+impl DaemonConfig {
+    /// Read the config file; a missing file means no overrides.
+    fn load(path: &Path) -> Result<Self> {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(e).wrap_err_with(|| format!("Failed to read {}", path.display())),
+        };
+        let words = shlex::split(&contents)
+            .ok_or_else(|| eyre!("Unbalanced quotes in {}", path.display()))?;
+        Self::try_parse_from(words).wrap_err_with(|| format!("Invalid config {}", path.display()))
+    }
+}
+
+/// Open the storage the daemon serves, applying the daemon config file.
+pub(crate) async fn daemon_storage(
+    cli: &Cli,
+    paths: &DaemonPaths,
+) -> Result<TopicStorage<Box<dyn EmbeddingModel>>> {
+    let config = DaemonConfig::load(&paths.config_file)?;
+    cli.open_storage(
+        config.provider.as_deref().unwrap_or(cli.provider()),
+        config
+            .qdrant_endpoint
+            .as_deref()
+            .unwrap_or(&cli.qdrant_endpoint),
+    )
+    .await
+}
+
+/// REST gateway requested by `--http-addr`, with the token read from `--http-token-file`.
+pub(crate) fn http_gateway(options: &DaemonOptions) -> Result<Option<HttpGateway>> {
+    let (Some(addr), Some(path)) = (options.http_addr, &options.http_token_file) else {
+        return Ok(None);
+    };
+    let token =
+        fs::read_to_string(path).wrap_err_with(|| format!("Failed to read {}", path.display()))?;
+    let token = token.trim();
+    if token.is_empty() {
+        return Err(eyre!("{} contains no token", path.display()));
+    }
+    Ok(Some(HttpGateway::new(addr, token)))
+}
+
+/// Start the daemon with this invocation's settings and wait until it accepts connections.
+pub(crate) async fn spawn_daemon(cli: &Cli, options: &DaemonOptions) -> Result<DaemonClient> {
+    let status = Command::new(std::env::current_exe()?)
+        .args(cli.daemon_args())
+        .args(["daemon", "start"])
+        .args(options.args())
+        .stdin(Stdio::null())
+        .status()
+        .wrap_err("Failed to spawn the daemon")?;
+    if !status.success() {
+        return Err(eyre!("Daemon failed to start ({})", status));
+    }
+
+    let started = Instant::now();
+    loop {
+        match DaemonClient::connect_to(cli.daemon_socket()).await {
+            Ok(client) => return Ok(client),
+            Err(ClientError::Connect(_)) if started.elapsed() < DAEMON_START_TIMEOUT => {
+                tokio::time::sleep(DAEMON_POLL_INTERVAL).await;
+            }
+            Err(e) => return Err(e).wrap_err("Daemon did not come up"),
+        }
+    }
+}
+
+/// Stop the daemon, asking it over the socket and falling back to SIGTERM.
+///
+/// Returns whether a daemon was running.
+pub(crate) async fn stop_daemon(cli: &Cli, paths: &DaemonPaths) -> Result<bool> {
+    match DaemonClient::connect_to(cli.daemon_socket()).await {
+        Ok(mut client) => client.shutdown().await?,
+        Err(e) => match paths.running_pid() {
+            Some(pid) => {
+                tracing::warn!("Daemon is not answering ({e}), sending SIGTERM");
+                kill(Pid::from_raw(pid), Signal::SIGTERM)
+                    .wrap_err_with(|| format!("Failed to signal daemon (pid {})", pid))?;
+            }
+            None => return Ok(false),
+        },
+    }
+
+    let started = Instant::now();
+    while paths.running_pid().is_some() {
+        if started.elapsed() > DAEMON_STOP_TIMEOUT {
+            return Err(eyre!(
+                "Daemon did not exit within {:?}",
+                DAEMON_STOP_TIMEOUT
+            ));
+        }
+        tokio::time::sleep(DAEMON_POLL_INTERVAL).await;
+    }
+    Ok(true)
+}
+
+/// Write systemd user units running the daemon with this invocation's settings.
+///
+/// Returns the directory the units were written to.
+pub(crate) fn install_units(cli: &Cli, options: &DaemonOptions) -> Result<PathBuf> {
+    let mut command = vec![std::env::current_exe()?.to_string_lossy().into_owned()];
+    command.extend(cli.daemon_args());
+    command.extend(["daemon", "start", "--foreground"].map(String::from));
+    command.extend(options.args());
+    let exec_start = shlex::try_join(command.iter().map(String::as_str))
+        .wrap_err("Daemon arguments can't be written to a unit file")?
+        // systemd expands specifiers and variables in ExecStart.
+        .replace('%', "%%")
+        .replace('$', "$$");
+    systemd::install_units(&exec_start, &cli.daemon_socket())
+        .wrap_err("Failed to write the systemd units")
+}
+
+pub(crate) async fn print_status(cli: &Cli, paths: &DaemonPaths) -> Result<()> {
+    let mut client = match DaemonClient::connect_to(cli.daemon_socket()).await {
+        Ok(client) => client,
+        Err(ClientError::Connect(_)) => {
+            match paths.running_pid() {
+                Some(pid) => println!("Daemon (pid {}) is running but not listening", pid),
+                None => println!("Daemon is not running"),
+            }
+            return Ok(());
+        }
+        Err(e) => return Err(e.into()),
+    };
+    let status = client.status().await?;
+    println!("Daemon is running (pid {})", status.pid);
+    println!("  Uptime: {}", format_uptime(status.uptime_secs));
+    println!("  Provider: {}", status.provider);
+    // Daemons older than protocol version 4 don't answer `Info`.
+    match client.info().await {
+        Ok(info) => {
+            let points = info
+                .points
+                .map_or("unknown".to_string(), |points| points.to_string());
+            println!("  Collection: {} ({} points)", info.collection, points);
+            println!(
+                "  Protocol: {}-{}",
+                info.min_protocol_version, info.protocol_version
+            );
+        }
+        Err(_) => println!("  Collection: {}", status.collection),
+    }
+    println!(
+        "  Requests: {} ({} failed)",
+        status.requests, status.failed_requests
+    );
+    println!("  Logs: {}", paths.log_dir.display());
+    Ok(())
+}
+
+/// Print whether the daemon reaches Qdrant and its embedding provider,
+/// failing if it doesn't.
+pub(crate) async fn check_health(cli: &Cli) -> Result<()> {
+    let health = DaemonClient::connect_to(cli.daemon_socket())
+        .await?
+        .health()
+        .await?;
+    match &health.qdrant {
+        Ok(version) => println!("✅ Qdrant {} is reachable", version),
+        Err(e) => println!("❌ {}", e),
+    }
+    match &health.embedding {
+        Ok(()) => println!("✅ Embedding provider is healthy"),
+        Err(e) => println!("❌ {}", e),
+    }
+    if !health.is_healthy() {
+        return Err(eyre!("Daemon is unhealthy"));
+    }
+    Ok(())
+}
+
+fn format_uptime(secs: u64) -> String {
+    let (days, hours, minutes, secs) = (secs / 86400, secs / 3600 % 24, secs / 60 % 60, secs % 60);
+    if days > 0 {
+        format!("{}d {}h {}m", days, hours, minutes)
+    } else if hours > 0 {
+        format!("{}h {}m {}s", hours, minutes, secs)
+    } else {
+        format!("{}m {}s", minutes, secs)
+    }
+}
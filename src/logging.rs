@@ -0,0 +1,82 @@
+use clap::ValueEnum;
+use eyre::{Result, WrapErr, eyre};
+use std::{io::IsTerminal, path::Path};
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::{
+    EnvFilter,
+    fmt::{MakeWriter, format::FmtSpan},
+};
+
+/// Filter used by commands when neither `--log-level` nor `RUST_LOG` is set.
+pub(crate) const DEFAULT_CLI_FILTER: &str = "warn";
+/// Filter used by the daemon when neither `--log-level` nor `RUST_LOG` is set.
+pub(crate) const DEFAULT_DAEMON_FILTER: &str = "info";
+/// Number of daily daemon log files kept before the oldest is deleted.
+pub(crate) const MAX_DAEMON_LOG_FILES: usize = 7;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub(crate) enum LogFormat {
+    /// Human-readable lines
+    #[default]
+    Pretty,
+    /// One JSON object per event, including the fields of its spans
+    Json,
+}
+
+/// Install the global subscriber, writing to stderr.
+pub(crate) fn init_stderr(
+    filter: Option<&str>,
+    default_filter: &str,
+    format: LogFormat,
+) -> Result<()> {
+    let ansi = std::io::stderr().is_terminal();
+    init(filter, default_filter, format, ansi, std::io::stderr)
+}
+
+/// Install the global subscriber for the daemon, writing to a log file in
+/// `log_dir` that is rotated daily.
+///
+/// Panics are logged too, since the daemon has no terminal to print them to.
+pub(crate) fn init_daemon(filter: Option<&str>, format: LogFormat, log_dir: &Path) -> Result<()> {
+    let appender = RollingFileAppender::builder()
+        .rotation(Rotation::DAILY)
+        .filename_prefix("daemon")
+        .filename_suffix("log")
+        .max_log_files(MAX_DAEMON_LOG_FILES)
+        .build(log_dir)
+        .wrap_err_with(|| format!("Failed to open the log in {}", log_dir.display()))?;
+    init(filter, DEFAULT_DAEMON_FILTER, format, false, appender)?;
+    std::panic::set_hook(Box::new(|info| tracing::error!("{info}")));
+    Ok(())
+}
+
+/// `filter` takes precedence over `RUST_LOG`, which takes precedence over
+/// `default_filter`. Spans report their duration when they close.
+fn init<W>(
+    filter: Option<&str>,
+    default_filter: &str,
+    format: LogFormat,
+    ansi: bool,
+    writer: W,
+) -> Result<()>
+where
+    W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+{
+    let filter = match filter {
+        Some(filter) => EnvFilter::try_new(filter)
+            .wrap_err_with(|| format!("Invalid log filter '{}'", filter))?,
+        None => {
+            EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(default_filter))
+        }
+    };
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_span_events(FmtSpan::CLOSE)
+        .with_ansi(ansi)
+        .with_writer(writer);
+    match format {
+        LogFormat::Pretty => builder.try_init(),
+        LogFormat::Json => builder.json().with_current_span(true).try_init(),
+    }
+    .map_err(|e| eyre!(e))
+}
@@ -1,14 +1,29 @@
-use clap::{Parser, Subcommand};
-use eyre::Result;
+use backend::IdeaBackend;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use daemon::{
+    daemon::{Daemon, DaemonSocket, run_daemon, run_foreground},
+    paths::DaemonPaths,
+    systemd,
+};
+use embedding::EmbeddingModel;
+use eyre::{Result, eyre};
+use logging::LogFormat;
+use providers::{Provider, ProviderCommands};
+use std::{net::SocketAddr, path::PathBuf, sync::Arc, time::Instant};
+use storage_client::{ChunkingConfig, TopicStorage};
 
+mod backend;
+mod daemon_control;
+mod logging;
 mod providers;
-mod daemon;
+
+/// Embedding provider used when `--provider` isn't given.
+const DEFAULT_PROVIDER: &str = "hugging-face";
 
 #[derive(Parser)]
 #[command(name = "qdrant-cli")]
 #[command(about)]
 #[command(version)]
-
 /// CLI for Qdrant-based topic storage
 struct Cli {
     #[command(subcommand)]
@@ -17,6 +32,124 @@ struct Cli {
     #[arg(long, short, default_value = "http://localhost:6334")]
     /// Qdrant endpoint
     qdrant_endpoint: String,
+
+    #[arg(long, short)]
+    /// Qdrant collection (derived from the embedding model if not specified)
+    collection: Option<String>,
+
+    #[arg(long)]
+    /// Split ideas longer than this many words into overlapping chunks
+    chunk_words: Option<usize>,
+
+    #[arg(long)]
+    /// Number of words shared by consecutive chunks (40 by default)
+    chunk_overlap: Option<usize>,
+
+    #[arg(long, short)]
+    /// Embedding provider for idea commands, written like its `provider` subcommand (e.g. "ollama --model nomic-embed-text"; "hugging-face" by default)
+    provider: Option<String>,
+
+    #[arg(long, conflicts_with = "start_daemon")]
+    /// Run idea commands in this process even if a daemon is running
+    no_daemon: bool,
+
+    #[arg(long)]
+    /// Start the daemon in the background if it isn't running yet
+    start_daemon: bool,
+
+    #[arg(long, value_name = "PATH")]
+    /// Reach the daemon through a socket file only its owner can open instead of the abstract `qdrant-cli-daemon.sock`
+    socket: Option<PathBuf>,
+
+    #[arg(long, value_name = "FILTER")]
+    /// Log filter such as "debug" or "info,storage_client=debug" (overrides RUST_LOG)
+    log_level: Option<String>,
+
+    #[arg(long, value_enum, default_value_t)]
+    /// Format of log lines
+    log_format: LogFormat,
+}
+
+impl Cli {
+    fn provider(&self) -> &str {
+        self.provider.as_deref().unwrap_or(DEFAULT_PROVIDER)
+    }
+
+    fn chunk_overlap(&self) -> usize {
+        self.chunk_overlap
+            .unwrap_or(ChunkingConfig::default().overlap_words)
+    }
+
+    /// Whether storage settings were given that a running daemon may not share.
+    fn sets_storage(&self) -> bool {
+        self.provider.is_some()
+            || self.collection.is_some()
+            || self.chunk_words.is_some()
+            || self.chunk_overlap.is_some()
+    }
+
+    /// Open the topic storage with the embedding model selected by `--provider`.
+    async fn storage(&self) -> Result<TopicStorage<Box<dyn EmbeddingModel>>> {
+        self.open_storage(self.provider(), &self.qdrant_endpoint)
+            .await
+    }
+
+    /// Open the topic storage with another provider or endpoint, keeping the
+    /// remaining settings.
+    async fn open_storage(
+        &self,
+        provider: &str,
+        qdrant_endpoint: &str,
+    ) -> Result<TopicStorage<Box<dyn EmbeddingModel>>> {
+        let embedding_model = Provider::from_spec(provider)?
+            .into_embedding_model()
+            .await?;
+        let mut storage = TopicStorage::new(qdrant_endpoint, embedding_model).await?;
+        if let Some(collection) = &self.collection {
+            storage = storage.with_collection_name(collection);
+        }
+        if let Some(max_words) = self.chunk_words {
+            storage = storage.with_chunking(ChunkingConfig {
+                max_words,
+                overlap_words: self.chunk_overlap(),
+            });
+        }
+        Ok(storage)
+    }
+
+    /// Global arguments that make a spawned daemon use this invocation's settings.
+    fn daemon_args(&self) -> Vec<String> {
+        let mut args = vec![
+            "--qdrant-endpoint".to_string(),
+            self.qdrant_endpoint.clone(),
+            "--provider".to_string(),
+            self.provider().to_string(),
+            "--chunk-overlap".to_string(),
+            self.chunk_overlap().to_string(),
+        ];
+        if let Some(collection) = &self.collection {
+            args.extend(["--collection".to_string(), collection.clone()]);
+        }
+        if let Some(chunk_words) = self.chunk_words {
+            args.extend(["--chunk-words".to_string(), chunk_words.to_string()]);
+        }
+        if let Some(socket) = &self.socket {
+            args.extend(["--socket".to_string(), socket.display().to_string()]);
+        }
+        if let Some(log_level) = &self.log_level {
+            args.extend(["--log-level".to_string(), log_level.clone()]);
+        }
+        if let Some(format) = self.log_format.to_possible_value() {
+            args.extend(["--log-format".to_string(), format.get_name().to_string()]);
+        }
+        args
+    }
+
+    fn daemon_socket(&self) -> DaemonSocket {
+        self.socket
+            .clone()
+            .map_or(DaemonSocket::Namespaced, DaemonSocket::Path)
+    }
 }
 
 /// Main CLI commands for the Qdrant-based topic storage system
@@ -30,10 +163,88 @@ pub(crate) enum Commands {
     /// Configure embedding model providers
     Provider {
         #[command(subcommand)]
-        subcommand: providers::Provider,
+        subcommand: providers::ProviderCommands,
+    },
+    /// Manage the background daemon serving idea commands
+    Daemon {
+        #[command(subcommand)]
+        subcommand: DaemonCommands,
+    },
+}
+
+/// Commands for managing the background daemon
+#[derive(Subcommand, Debug)]
+pub(crate) enum DaemonCommands {
+    /// Start the daemon with the given provider and Qdrant settings
+    Start {
+        #[arg(long)]
+        /// Stay in the foreground and log to stderr, as systemd expects
+        foreground: bool,
+
+        #[command(flatten)]
+        options: DaemonOptions,
+    },
+    /// Stop the running daemon
+    Stop,
+    /// Show whether the daemon is running and what it serves
+    Status,
+    /// Check that the daemon can reach Qdrant and its embedding provider
+    Health,
+    /// Stop the running daemon and start it with the given settings
+    Restart {
+        #[command(flatten)]
+        options: DaemonOptions,
+    },
+    /// Write systemd user units that start the daemon with the given settings on first use
+    InstallUnit {
+        #[command(flatten)]
+        options: DaemonOptions,
     },
 }
 
+/// Settings of a daemon being started
+#[derive(Args, Debug, Default)]
+pub(crate) struct DaemonOptions {
+    #[arg(long = "allow-uid", value_name = "UID")]
+    /// Also accept connections from this user; only the daemon's own user is accepted otherwise
+    allow_uids: Vec<u32>,
+
+    #[arg(long, value_name = "ADDR")]
+    /// Serve Prometheus metrics on http://ADDR/metrics, e.g. 127.0.0.1:9464
+    metrics_addr: Option<SocketAddr>,
+
+    #[arg(long, value_name = "ADDR", requires = "http_token_file")]
+    /// Serve the REST gateway on http://ADDR, e.g. 127.0.0.1:8080
+    http_addr: Option<SocketAddr>,
+
+    #[arg(long, value_name = "PATH", requires = "http_addr")]
+    /// File containing the bearer token REST clients must send
+    http_token_file: Option<PathBuf>,
+}
+
+impl DaemonOptions {
+    /// Arguments of `daemon start` that apply these options.
+    fn args(&self) -> Vec<String> {
+        let mut args: Vec<String> = self
+            .allow_uids
+            .iter()
+            .flat_map(|uid| ["--allow-uid".to_string(), uid.to_string()])
+            .collect();
+        if let Some(addr) = self.metrics_addr {
+            args.extend(["--metrics-addr".to_string(), addr.to_string()]);
+        }
+        if let Some(addr) = self.http_addr {
+            args.extend(["--http-addr".to_string(), addr.to_string()]);
+        }
+        if let Some(path) = &self.http_token_file {
+            // Units and spawned daemons may run from another directory.
+            let path = std::path::absolute(path).unwrap_or_else(|_| path.clone());
+            args.extend(["--http-token-file".to_string(), path.display().to_string()]);
+        }
+        args
+    }
+}
+
 /// Commands for managing ideas within topics
 #[derive(Subcommand, Debug)]
 pub(crate) enum IdeaCommands {
@@ -70,78 +281,203 @@ pub(crate) enum IdeaCommands {
         /// Maximum number of ideas to list
         limit: u32,
     },
+
+    /// Delete an idea by the id shown by `search` and `list`
+    Delete {
+        /// Id of the idea to delete
+        id: u64,
+    },
+
+    /// Re-embed all ideas of another collection into the current model's collection
+    Reindex {
+        #[arg(short, long)]
+        /// Collection to migrate ideas from
+        from: String,
+    },
 }
 
-#[tokio::main]
-async fn main() -> Result<()> {
+fn main() -> Result<()> {
     let cli = Cli::parse();
 
-    match cli.command {
-        Commands::Idea { subcommand } => match subcommand {
-            IdeaCommands::New { topic, content } => {
-                println!("Creating new topic: {}", topic);
-                // TODO: Implement storage creation with embedding model
-                // let storage = TopicStorage::new(&cli.qdrant_endpoint, embedding_model).await?;
-                // storage.create_topic(&topic, &content).await?;
-                println!("✅ Topic '{}' created successfully!", topic);
+    // Daemonizing forks, which has to happen before the runtime starts its threads.
+    if let Commands::Daemon {
+        subcommand:
+            DaemonCommands::Start {
+                foreground,
+                ref options,
+            },
+    } = cli.command
+    {
+        let paths = DaemonPaths::from_env()?;
+        if let Some(pid) = paths.running_pid() {
+            return Err(eyre!("Daemon is already running (pid {})", pid));
+        }
+        if foreground {
+            logging::init_stderr(
+                cli.log_level.as_deref(),
+                logging::DEFAULT_DAEMON_FILTER,
+                cli.log_format,
+            )?;
+        } else {
+            logging::init_daemon(cli.log_level.as_deref(), cli.log_format, &paths.log_dir)?;
+        }
+        let activation_socket = systemd::activation_socket()?;
+        let allowed_uids = options.allow_uids.clone();
+        let metrics_addr = options.metrics_addr;
+        // Read before detaching, so a bad token file is reported to the caller.
+        let http_gateway = daemon_control::http_gateway(options)?;
+        let config_paths = paths.clone();
+        let serve = || async move {
+            if let Some(addr) = metrics_addr {
+                daemon::metrics::install_exporter(addr)?;
             }
+            let storage = daemon_control::daemon_storage(&cli, &config_paths).await?;
+            Arc::new(
+                Daemon::new(storage, bincode::config::standard())
+                    .with_socket(cli.daemon_socket())
+                    .with_allowed_uids(allowed_uids)
+                    .with_activation_socket(activation_socket)
+                    .with_http_gateway(http_gateway),
+            )
+            .serve(|| daemon_control::daemon_storage(&cli, &config_paths))
+            .await
+        };
+        return if foreground {
+            run_foreground(serve)
+        } else {
+            run_daemon(&paths, serve)
+        };
+    }
+
+    logging::init_stderr(
+        cli.log_level.as_deref(),
+        logging::DEFAULT_CLI_FILTER,
+        cli.log_format,
+    )?;
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()?
+        .block_on(run(cli))
+}
 
-            IdeaCommands::Search {
-                topic,
-                query,
-                limit,
-            } => {
-                let results: Vec<String> = if let Some(topic) = topic {
-                    println!("Searching in topic '{}' for: {}", topic, query);
-                    // TODO: Implement search with embedding model
-                    // storage.search_topic(Some(&topic), &query, limit).await?
-                    vec![] // Placeholder
-                } else {
-                    println!("Searching for: {}", query);
-                    // TODO: Implement search with embedding model
-                    // storage.search_topic(None, &query, limit).await?
-                    vec![] // Placeholder
-                };
-
-                if results.is_empty() {
-                    println!("No results found.");
-                } else {
-                    println!("Found {} results:", results.len());
-                    for (i, result) in results.iter().enumerate() {
-                        println!("  {}. {}", i + 1, result);
+async fn run(cli: Cli) -> Result<()> {
+    match cli.command {
+        Commands::Idea { ref subcommand } => {
+            match subcommand {
+                IdeaCommands::New { topic, content } => {
+                    println!("Creating new topic: {}", topic);
+                    let mut backend = IdeaBackend::open(&cli).await?;
+                    let id = backend.create_idea(topic, content).await?;
+                    println!("✅ Idea {} created in topic '{}'", id, topic);
+                }
+
+                IdeaCommands::Search {
+                    topic,
+                    query,
+                    limit,
+                } => {
+                    let mut backend = IdeaBackend::open(&cli).await?;
+                    let results = if let Some(topic) = topic {
+                        println!("Searching in topic '{}' for: {}", topic, query);
+                        backend.search(Some(topic), query, *limit).await?
+                    } else {
+                        println!("Searching for: {}", query);
+                        backend.search(None, query, *limit).await?
+                    };
+
+                    if results.is_empty() {
+                        println!("No results found.");
+                    } else {
+                        println!("Found {} results:", results.len());
+                        for (i, result) in results.iter().enumerate() {
+                            println!("  {}. [{}] {}", i + 1, result.id, result.content);
+                            // Long ideas are matched by one of their chunks.
+                            if let Some(snippet) = &result.snippet {
+                                println!("     > {}", snippet);
+                            }
+                        }
                     }
                 }
-            }
 
-            IdeaCommands::List { topic, limit } => {
-                println!("Listing topics in '{}' (limit: {})", topic, limit);
-                // TODO: Implement list with embedding model
-                // let results = storage.list_topic(&topic, limit).await?;
-                let results: Vec<String> = vec![]; // Placeholder
-
-                if results.is_empty() {
-                    println!("No topics found.");
-                } else {
-                    println!("Found {} topics:", results.len());
-                    for (i, result) in results.iter().enumerate() {
-                        println!("  {}. {}", i + 1, result);
+                IdeaCommands::List { topic, limit } => {
+                    println!("Listing topics in '{}' (limit: {})", topic, limit);
+                    let mut backend = IdeaBackend::open(&cli).await?;
+                    let results = backend.list(topic, *limit).await?;
+
+                    if results.is_empty() {
+                        println!("No topics found.");
+                    } else {
+                        println!("Found {} topics:", results.len());
+                        for (i, result) in results.iter().enumerate() {
+                            println!("  {}. [{}] {}", i + 1, result.id, result.content);
+                        }
                     }
                 }
+
+                IdeaCommands::Delete { id } => {
+                    let mut backend = IdeaBackend::open(&cli).await?;
+                    backend.delete(*id).await?;
+                    println!("✅ Idea {} deleted", id);
+                }
+
+                IdeaCommands::Reindex { from } => {
+                    println!("Reindexing ideas from collection '{}'", from);
+                    // Long-running maintenance, always done in this process.
+                    let reindexed = cli.storage().await?.reindex_from(from).await?;
+                    println!("✅ Reindexed {} ideas", reindexed);
+                }
+            }
+        }
+
+        Commands::Provider { subcommand } => match subcommand {
+            ProviderCommands::Check { provider } => {
+                let embedding_model = provider.into_embedding_model().await?;
+                println!("Checking {}...", embedding_model.model_info());
+                let started = Instant::now();
+                embedding_model.health_check().await?;
+                println!(
+                    "✅ Provider is healthy ({} ms)",
+                    started.elapsed().as_millis()
+                );
+            }
+
+            ProviderCommands::Create(provider) => {
+                println!("Creating embedding model from provider configuration...");
+                let embedding_model = provider.into_embedding_model().await?;
+                println!("✅ Embedding model created successfully!");
+                println!("Model: {}", embedding_model.model_info());
             }
         },
 
-        Commands::Provider { subcommand } => {
-            println!("Creating embedding model from provider configuration...");
-            let embedding_model = subcommand.into_embedding_model().await?;
-            println!("✅ Embedding model created successfully!");
-            // Example:
-            // let storage = TopicStorage::new(&cli.qdrant_endpoint, embedding_model).await?;
-
-            // For demonstration, just print the model type
-            println!(
-                "Model type: {:?}",
-                std::any::type_name_of_val(&*embedding_model)
-            );
+        Commands::Daemon { ref subcommand } => {
+            let paths = DaemonPaths::from_env()?;
+            match subcommand {
+                DaemonCommands::Start { .. } => {
+                    unreachable!("the daemon is started before the runtime")
+                }
+                DaemonCommands::Stop => {
+                    if daemon_control::stop_daemon(&cli, &paths).await? {
+                        println!("✅ Daemon stopped");
+                    } else {
+                        println!("Daemon is not running");
+                    }
+                }
+                DaemonCommands::Status => daemon_control::print_status(&cli, &paths).await?,
+                DaemonCommands::Health => daemon_control::check_health(&cli).await?,
+                DaemonCommands::InstallUnit { options } => {
+                    let unit_dir = daemon_control::install_units(&cli, options)?;
+                    println!("✅ Wrote systemd units to {}", unit_dir.display());
+                    println!(
+                        "Enable them with: systemctl --user daemon-reload && systemctl --user enable --now {}.socket",
+                        systemd::UNIT_NAME
+                    );
+                }
+                DaemonCommands::Restart { options } => {
+                    daemon_control::stop_daemon(&cli, &paths).await?;
+                    let mut client = daemon_control::spawn_daemon(&cli, options).await?;
+                    println!("✅ Daemon restarted (pid {})", client.status().await?.pid);
+                }
+            }
         }
     }
 
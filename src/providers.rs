@@ -1,6 +1,18 @@
-use clap::{Args, Subcommand};
-use embedding::{EmbeddingModel, hugging_face::HuggingFace, self_hosted::SelfHosted};
-use eyre::{Result, WrapErr};
+use clap::{Args, Parser, Subcommand};
+#[cfg(feature = "local")]
+use embedding::local::LocalModel;
+use embedding::{
+    Distance, EmbeddingModel, InstructionPrefixes, ModelInfo,
+    credentials::{ApiKeyResolver, SecretString},
+    fallback::{self, FallbackEmbedder},
+    hashing::{self, HashingEmbedder},
+    hugging_face::HuggingFace,
+    ollama::{self, KeepAlive, Ollama},
+    self_hosted::SelfHosted,
+    tei::TextEmbeddingsInference,
+};
+use eyre::{Result, WrapErr, eyre};
+use std::{path::PathBuf, time::Duration};
 
 /// Provider configuration for different embedding model services
 ///
@@ -8,21 +20,53 @@ use eyre::{Result, WrapErr};
 /// through CLI subcommands, which can then be converted into EmbeddingModel
 /// instances for use with the storage system.
 ///
-/// # Use HuggingFace provider
-/// 
-/// qdrant-cli provider hugging-face --api-key YOUR_KEY --embedding-endpoint "https://..."
+/// # Use HuggingFace provider with the key from HF_TOKEN or `huggingface-cli login`
+///
+/// qdrant-cli provider hugging-face --embedding-endpoint "https://..."
+///
+/// # Use a BGE model with its query instruction
+///
+/// qdrant-cli provider hugging-face --api-key-file ~/.secrets/hf --query-prefix "Represent this sentence for searching relevant passages: "
 ///
 /// # Use self-hosted provider
-/// 
-/// qdrant-cli provider self-hosted --embedding-endpoint "http://localhost:8000"
+///
+/// qdrant-cli provider self-hosted --embedding-endpoint "http://localhost:8000" --model-id my-model --dimension 384
+///
+/// # Use a local Ollama instance
+///
+/// qdrant-cli provider ollama --model nomic-embed-text --keep-alive 10m
+///
+/// # Use a Text Embeddings Inference server
+///
+/// qdrant-cli provider tei --embedding-endpoint "http://localhost:8080" --model-id BAAI/bge-small-en-v1.5 --dimension 384 --query-prompt-name query
+///
+/// # Let a Text Embeddings Inference server report its model and dimension
+///
+/// qdrant-cli provider tei --embedding-endpoint "http://localhost:8080" --fetch-info
+///
+/// # Use the deterministic hashing embedder (no model, no network)
+///
+/// qdrant-cli provider hashing --dimension 384
+///
+/// # Use a model from a local directory (requires the `local` feature)
+///
+/// qdrant-cli provider local --model-dir ~/models/bge-small-en-v1.5
+///
+/// # Fall back from a local TEI server to the hosted Hugging Face API
+///
+/// qdrant-cli provider fallback "tei --model-id BAAI/bge-base-en-v1.5 --dimension 768" "hugging-face"
 /// ```
 #[derive(Subcommand, Debug)]
 pub(crate) enum Provider {
     /// Hugging Face embedding provider
     HuggingFace {
+        #[arg(long, conflicts_with = "api_key_file")]
+        /// Hugging Face API key (visible in shell history; prefer the other sources)
+        api_key: Option<SecretString>,
+
         #[arg(long)]
-        /// Hugging Face API key
-        api_key: String,
+        /// File containing the Hugging Face API key
+        api_key_file: Option<PathBuf>,
 
         #[arg(
             short,
@@ -31,6 +75,13 @@ pub(crate) enum Provider {
         )]
         /// Hugging Face endpoint
         embedding_endpoint: String,
+
+        #[arg(long, default_value = "768")]
+        /// Dimension of the embeddings returned by the endpoint
+        dimension: usize,
+
+        #[command(flatten)]
+        options: ModelArgs,
     },
 
     /// Self-hosted embedding provider
@@ -38,10 +89,175 @@ pub(crate) enum Provider {
         #[arg(short, long)]
         /// Self-hosted embedding service endpoint
         embedding_endpoint: String,
+
+        #[arg(long)]
+        /// Identifier of the served model
+        model_id: String,
+
+        #[arg(long)]
+        /// Dimension of the embeddings returned by the service
+        dimension: usize,
+
+        #[command(flatten)]
+        options: ModelArgs,
+    },
+
+    /// Local Ollama embedding provider
+    Ollama {
+        #[arg(short, long, default_value = ollama::DEFAULT_ENDPOINT)]
+        /// Ollama server endpoint
+        embedding_endpoint: String,
+
+        #[arg(short, long, default_value = "nomic-embed-text")]
+        /// Name of the Ollama embedding model
+        model: String,
+
+        #[arg(long, default_value = "768")]
+        /// Dimension of the embeddings produced by the model
+        dimension: usize,
+
+        #[arg(long, allow_negative_numbers = true)]
+        /// How long Ollama keeps the model loaded (e.g. "5m", or seconds; negative keeps it loaded)
+        keep_alive: Option<KeepAlive>,
+
+        #[command(flatten)]
+        options: ModelArgs,
+    },
+
+    /// HuggingFace Text Embeddings Inference provider
+    Tei {
+        #[arg(short, long, default_value = "http://localhost:8080")]
+        /// Text Embeddings Inference server endpoint
+        embedding_endpoint: String,
+
+        #[arg(long)]
+        /// Truncate inputs longer than the model's maximum input length
+        truncate: Option<bool>,
+
+        #[arg(long)]
+        /// Normalize the returned embeddings
+        normalize: Option<bool>,
+
+        #[arg(long)]
+        /// Prompt name used when embedding stored ideas (e.g. "passage")
+        prompt_name: Option<String>,
+
+        #[arg(long)]
+        /// Prompt name used when embedding search queries (e.g. "query")
+        query_prompt_name: Option<String>,
+
+        #[arg(long, required_unless_present = "fetch_info")]
+        /// Identifier of the served model, as reported by the server's /info route
+        model_id: Option<String>,
+
+        #[arg(long, required_unless_present = "fetch_info")]
+        /// Dimension of the embeddings returned by the server
+        dimension: Option<usize>,
+
+        #[arg(long, conflicts_with_all = ["model_id", "dimension"])]
+        /// Ask the server for the model id and dimension instead of passing them
+        fetch_info: bool,
+
+        #[command(flatten)]
+        options: ModelArgs,
+    },
+
+    /// Deterministic feature-hashing embedder, for tests and air-gapped use
+    Hashing {
+        #[arg(long, default_value_t = hashing::DEFAULT_DIMENSION)]
+        /// Dimension of the embeddings
+        dimension: usize,
+    },
+
+    /// Offline sentence-transformer model running on the CPU
+    #[cfg(feature = "local")]
+    Local {
+        #[arg(long)]
+        /// Directory containing config.json, tokenizer.json and model.safetensors
+        model_dir: PathBuf,
+
+        #[arg(long)]
+        /// Normalize the pooled embeddings
+        normalize: Option<bool>,
+
+        #[command(flatten)]
+        options: ModelArgs,
+    },
+
+    /// Chain of providers serving the same model, tried in order
+    Fallback {
+        #[arg(required = true, num_args = 1..)]
+        /// Provider configurations in priority order, each quoted as a single argument (e.g. "ollama --model nomic-embed-text")
+        chain: Vec<String>,
+
+        #[arg(long, default_value_t = fallback::DEFAULT_FAILURE_THRESHOLD)]
+        /// Consecutive failures after which a provider is skipped
+        failure_threshold: u32,
+
+        #[arg(long, default_value_t = fallback::DEFAULT_COOLDOWN.as_secs())]
+        /// Seconds a failing provider is skipped before it is retried
+        cooldown_secs: u64,
     },
 }
 
+/// A provider configuration given as a single string, such as a fallback
+/// chain entry or the `--provider` option
+#[derive(Parser, Debug)]
+#[command(no_binary_name = true)]
+struct ProviderSpec {
+    #[command(subcommand)]
+    provider: Provider,
+}
+
+/// Commands for configuring embedding model providers
+#[derive(Subcommand, Debug)]
+pub(crate) enum ProviderCommands {
+    /// Check credentials and embed a probe input with a provider
+    Check {
+        #[command(subcommand)]
+        provider: Provider,
+    },
+
+    #[command(flatten)]
+    Create(Provider),
+}
+
+/// Embedding options shared by all providers
+#[derive(Args, Debug)]
+pub(crate) struct ModelArgs {
+    #[arg(long, default_value = "cosine")]
+    /// Distance function used to compare embeddings (cosine, dot, euclid, manhattan)
+    distance: Distance,
+
+    #[arg(long)]
+    /// Prefix prepended to search queries before embedding
+    query_prefix: Option<String>,
+
+    #[arg(long)]
+    /// Prefix prepended to ideas before embedding
+    document_prefix: Option<String>,
+}
+
+impl ModelArgs {
+    fn prefixes(&self) -> InstructionPrefixes {
+        InstructionPrefixes {
+            query: self.query_prefix.clone(),
+            document: self.document_prefix.clone(),
+        }
+    }
+}
+
 impl Provider {
+    /// Parse a provider configuration written like its subcommand, e.g.
+    /// `ollama --model nomic-embed-text`
+    pub fn from_spec(spec: &str) -> Result<Provider> {
+        let words =
+            shlex::split(spec).ok_or_else(|| eyre!("Unbalanced quotes in provider '{}'", spec))?;
+        let spec = ProviderSpec::try_parse_from(words)
+            .wrap_err_with(|| format!("Invalid provider '{}'", spec))?;
+        Ok(spec.provider)
+    }
+
     /// Convert the provider configuration into an EmbeddingModel
     ///
     /// This method takes ownership of the Provider and creates the corresponding
@@ -56,18 +272,156 @@ impl Provider {
         let embedding_model: Box<dyn EmbeddingModel> = match self {
             Provider::HuggingFace {
                 api_key,
+                api_key_file,
                 embedding_endpoint,
-            } => Box::new(
-                HuggingFace::new(api_key, embedding_endpoint)
+                dimension,
+                options,
+            } => {
+                let (api_key, _) = ApiKeyResolver::new()
+                    .with_api_key(api_key)
+                    .with_file(api_key_file)
+                    .resolve()
                     .await
-                    .wrap_err("Failed to create hugging face embedding model")?,
+                    .wrap_err("Failed to resolve hugging face api key")?;
+                Box::new(
+                    HuggingFace::new(api_key, embedding_endpoint, dimension)
+                        .await
+                        .wrap_err("Failed to create hugging face embedding model")?
+                        .with_distance(options.distance)
+                        .with_prefixes(options.prefixes()),
+                )
+            }
+            Provider::SelfHosted {
+                embedding_endpoint,
+                model_id,
+                dimension,
+                options,
+            } => Box::new(
+                SelfHosted::new(
+                    embedding_endpoint,
+                    ModelInfo::new(model_id, dimension).with_distance(options.distance),
+                )
+                .await
+                .wrap_err("Failed to create self hosted embedding model")?
+                .with_prefixes(options.prefixes()),
             ),
-            Provider::SelfHosted { embedding_endpoint } => Box::new(
-                SelfHosted::new(embedding_endpoint)
+            Provider::Ollama {
+                embedding_endpoint,
+                model,
+                dimension,
+                keep_alive,
+                options,
+            } => {
+                let ollama = Ollama::new(embedding_endpoint, model, dimension)
                     .await
-                    .wrap_err("Failed to create self hosted embedding model")?,
-            ),
+                    .wrap_err("Failed to create ollama embedding model")?
+                    .with_distance(options.distance)
+                    .with_prefixes(options.prefixes());
+                Box::new(match keep_alive {
+                    Some(keep_alive) => ollama.with_keep_alive(keep_alive),
+                    None => ollama,
+                })
+            }
+            Provider::Tei {
+                embedding_endpoint,
+                truncate,
+                normalize,
+                prompt_name,
+                query_prompt_name,
+                model_id,
+                dimension,
+                fetch_info,
+                options,
+            } => {
+                // Without --fetch-info clap requires both, and with it they are
+                // replaced by what the server reports.
+                let mut tei = TextEmbeddingsInference::new(
+                    embedding_endpoint,
+                    ModelInfo::new(model_id.unwrap_or_default(), dimension.unwrap_or_default()),
+                )
+                .await
+                .wrap_err("Failed to create text embeddings inference model")?
+                .with_distance(options.distance)
+                .with_prefixes(options.prefixes());
+                if let Some(truncate) = truncate {
+                    tei = tei.with_truncate(truncate);
+                }
+                if let Some(normalize) = normalize {
+                    tei = tei.with_normalize(normalize);
+                }
+                if let Some(prompt_name) = prompt_name {
+                    tei = tei.with_prompt_name(prompt_name);
+                }
+                if let Some(query_prompt_name) = query_prompt_name {
+                    tei = tei.with_query_prompt_name(query_prompt_name);
+                }
+                if fetch_info {
+                    tei.fetch_info()
+                        .await
+                        .wrap_err("Failed to fetch text embeddings inference model info")?;
+                }
+                Box::new(tei)
+            }
+            Provider::Hashing { dimension } => Box::new(HashingEmbedder::new(dimension)),
+            #[cfg(feature = "local")]
+            Provider::Local {
+                model_dir,
+                normalize,
+                options,
+            } => {
+                let local = LocalModel::new(&model_dir)
+                    .await
+                    .wrap_err("Failed to load local embedding model")?
+                    .with_distance(options.distance)
+                    .with_prefixes(options.prefixes());
+                Box::new(match normalize {
+                    Some(normalize) => local.with_normalize(normalize),
+                    None => local,
+                })
+            }
+            Provider::Fallback {
+                chain,
+                failure_threshold,
+                cooldown_secs,
+            } => {
+                let mut models = Vec::with_capacity(chain.len());
+                for entry in chain {
+                    let provider = Provider::from_spec(&entry)?;
+                    models.push(Box::pin(provider.into_embedding_model()).await?);
+                }
+                Box::new(
+                    FallbackEmbedder::new(models)
+                        .wrap_err("Failed to create fallback embedding model")?
+                        .with_failure_threshold(failure_threshold)
+                        .with_cooldown(Duration::from_secs(cooldown_secs)),
+                )
+            }
         };
         Ok(embedding_model)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_negative_keep_alive() {
+        match Provider::from_spec("ollama --keep-alive -1").unwrap() {
+            Provider::Ollama { keep_alive, .. } => {
+                assert_eq!(keep_alive, Some(KeepAlive::Seconds(-1)));
+            }
+            provider => panic!("Unexpected provider {:?}", provider),
+        }
+    }
+
+    #[test]
+    fn test_tei_model_is_given_or_fetched() {
+        assert!(Provider::from_spec("tei").is_err());
+        assert!(
+            Provider::from_spec("tei --model-id BAAI/bge-small-en-v1.5 --dimension 384").is_ok()
+        );
+        assert!(Provider::from_spec("tei --fetch-info").is_ok());
+        assert!(Provider::from_spec("tei --fetch-info --dimension 384").is_err());
+    }
+}
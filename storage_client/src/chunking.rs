@@ -0,0 +1,122 @@
+/// How long idea contents are split before embedding.
+///
+/// Budgets are counted in whitespace-separated words, which is a cheap and
+/// conservative stand-in for model tokens.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkingConfig {
+    /// Maximum number of words per chunk
+    pub max_words: usize,
+    /// Number of words repeated from the end of the previous chunk
+    pub overlap_words: usize,
+}
+
+impl Default for ChunkingConfig {
+    fn default() -> Self {
+        Self {
+            max_words: 200,
+            overlap_words: 40,
+        }
+    }
+}
+
+/// Split text into sentences, keeping their terminating punctuation.
+fn sentences(text: &str) -> Vec<&str> {
+    let mut sentences = Vec::new();
+    let mut start = 0;
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        let end = i + c.len_utf8();
+        let at_boundary = match c {
+            '\n' => true,
+            '.' | '!' | '?' => chars.peek().is_none_or(|(_, next)| next.is_whitespace()),
+            _ => false,
+        };
+        if at_boundary {
+            sentences.push(text[start..end].trim());
+            start = end;
+        }
+    }
+    sentences.push(text[start..].trim());
+    sentences.retain(|sentence| !sentence.is_empty());
+    sentences
+}
+
+/// Split `text` into overlapping chunks of at most `config.max_words` words.
+///
+/// Sentences are packed greedily; a sentence longer than the budget is split
+/// on word boundaries. Each chunk after the first starts with the last
+/// `config.overlap_words` words of the previous one, so context spanning a
+/// chunk boundary is still embedded together.
+pub fn split_into_chunks(text: &str, config: &ChunkingConfig) -> Vec<String> {
+    let max_words = config.max_words.max(1);
+    let overlap_words = config.overlap_words.min(max_words - 1);
+
+    let mut chunks: Vec<Vec<&str>> = Vec::new();
+    let mut current: Vec<&str> = Vec::new();
+    // Number of leading words of `current` carried over from the previous chunk.
+    let mut carried = 0;
+
+    for sentence in sentences(text) {
+        let words: Vec<&str> = sentence.split_whitespace().collect();
+        if current.len() + words.len() > max_words && current.len() > carried {
+            let overlap = current[current.len().saturating_sub(overlap_words)..].to_vec();
+            chunks.push(std::mem::replace(&mut current, overlap));
+            carried = current.len();
+        }
+        for word in words {
+            if current.len() == max_words {
+                let overlap = current[current.len() - overlap_words..].to_vec();
+                chunks.push(std::mem::replace(&mut current, overlap));
+                carried = current.len();
+            }
+            current.push(word);
+        }
+    }
+    if current.len() > carried || chunks.is_empty() {
+        chunks.push(current);
+    }
+
+    chunks.into_iter().map(|words| words.join(" ")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_short_text_is_a_single_chunk() {
+        let chunks = split_into_chunks("Learn to juggle. Then juggle.", &ChunkingConfig::default());
+        assert_eq!(chunks, vec!["Learn to juggle. Then juggle."]);
+    }
+
+    #[test]
+    fn test_chunks_respect_sentences_and_overlap() {
+        let config = ChunkingConfig {
+            max_words: 6,
+            overlap_words: 2,
+        };
+        let chunks = split_into_chunks("One two three. Four five six. Seven eight nine.", &config);
+        assert_eq!(
+            chunks,
+            vec![
+                "One two three. Four five six.",
+                "five six. Seven eight nine.",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_long_sentence_is_split_on_words() {
+        let config = ChunkingConfig {
+            max_words: 3,
+            overlap_words: 1,
+        };
+        let chunks = split_into_chunks("a b c d e", &config);
+        assert_eq!(chunks, vec!["a b c", "c d e"]);
+        assert!(
+            chunks
+                .iter()
+                .all(|chunk| chunk.split_whitespace().count() <= 3)
+        );
+    }
+}
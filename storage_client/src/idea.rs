@@ -1,53 +1,103 @@
-use qdrant_client::qdrant::{PointId, RetrievedPoint, ScoredPoint, point_id::PointIdOptions};
+use std::collections::HashMap;
 
-use crate::{TOPIC_CONTENT_KEY, TOPIC_NAME_KEY};
+use qdrant_client::qdrant::{
+    PointId, RetrievedPoint, ScoredPoint, Value, point_id::PointIdOptions, value::Kind,
+};
+
+use crate::storage::{
+    CHUNK_CONTENT_KEY, CHUNK_INDEX_KEY, PARENT_ID_KEY, TOPIC_CONTENT_KEY, TOPIC_NAME_KEY,
+};
 
 use super::Idea;
 
+/// Numeric id of a point, `None` for UUIDs, which this crate never assigns
+/// but other tools writing to a collection may.
 pub fn try_extract_u64_id(id: PointId) -> Option<u64> {
     match &id.point_id_options {
         Some(PointIdOptions::Num(num)) => Some(*num),
-        Some(PointIdOptions::Uuid(_)) => panic!("Expected u64 id, got UUID"),
-        None => None,
+        Some(PointIdOptions::Uuid(_)) | None => None,
     }
 }
 
-impl From<ScoredPoint> for Idea {
-    fn from(point: ScoredPoint) -> Self {
+/// Read a string payload field without the quoting `Value`'s `Display` adds.
+pub fn payload_string(payload: &HashMap<String, Value>, key: &str) -> String {
+    match payload.get(key).and_then(|value| value.kind.as_ref()) {
+        Some(Kind::StringValue(s)) => s.clone(),
+        Some(_) => payload[key].to_string(),
+        None => String::new(),
+    }
+}
+
+fn payload_integer(payload: &HashMap<String, Value>, key: &str) -> Option<i64> {
+    match payload.get(key).and_then(|value| value.kind.as_ref()) {
+        Some(Kind::IntegerValue(value)) => Some(*value),
+        _ => None,
+    }
+}
+
+/// Position of the point within a chunked idea, `None` for unchunked ideas.
+pub fn chunk_index(payload: &HashMap<String, Value>) -> Option<i64> {
+    payload_integer(payload, CHUNK_INDEX_KEY)
+}
+
+impl Idea {
+    /// Build an idea from a stored point.
+    ///
+    /// Chunks report the id of the idea they belong to and their own text as
+    /// the snippet.
+    fn from_point(id: Option<PointId>, payload: &HashMap<String, Value>) -> Self {
+        let parent_id = payload_integer(payload, PARENT_ID_KEY).map(|id| id as u64);
         Self {
-            id: point.id.and_then(try_extract_u64_id),
-            topic_name: point.payload.get(TOPIC_NAME_KEY).unwrap().to_string(),
-            content: point.payload.get(TOPIC_CONTENT_KEY).unwrap().to_string(),
+            id: parent_id.or_else(|| id.and_then(try_extract_u64_id)),
+            topic_name: payload_string(payload, TOPIC_NAME_KEY),
+            content: payload_string(payload, TOPIC_CONTENT_KEY),
+            snippet: payload
+                .contains_key(CHUNK_CONTENT_KEY)
+                .then(|| payload_string(payload, CHUNK_CONTENT_KEY)),
         }
     }
 }
 
+impl From<ScoredPoint> for Idea {
+    fn from(point: ScoredPoint) -> Self {
+        Self::from_point(point.id, &point.payload)
+    }
+}
+
 impl From<&ScoredPoint> for Idea {
     fn from(point: &ScoredPoint) -> Self {
-        Self {
-            id: point.id.clone().and_then(try_extract_u64_id),
-            topic_name: point.payload.get(TOPIC_NAME_KEY).unwrap().to_string(),
-            content: point.payload.get(TOPIC_CONTENT_KEY).unwrap().to_string(),
-        }
+        Self::from_point(point.id.clone(), &point.payload)
     }
 }
 
 impl From<RetrievedPoint> for Idea {
     fn from(point: RetrievedPoint) -> Self {
-        Self {
-            id: point.id.and_then(try_extract_u64_id),
-            topic_name: point.payload.get(TOPIC_NAME_KEY).unwrap().to_string(),
-            content: point.payload.get(TOPIC_CONTENT_KEY).unwrap().to_string(),
-        }
+        Self::from_point(point.id, &point.payload)
     }
 }
 
 impl From<&RetrievedPoint> for Idea {
     fn from(point: &RetrievedPoint) -> Self {
-        Self {
-            id: point.id.clone().and_then(try_extract_u64_id),
-            topic_name: point.payload.get(TOPIC_NAME_KEY).unwrap().to_string(),
-            content: point.payload.get(TOPIC_CONTENT_KEY).unwrap().to_string(),
-        }
+        Self::from_point(point.id.clone(), &point.payload)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_uuid_points_have_no_idea_id() {
+        let point = RetrievedPoint {
+            id: Some(PointId::from(
+                "3f2504e0-4f89-11d3-9a0c-0305e82c3301".to_string(),
+            )),
+            payload: HashMap::from([(TOPIC_CONTENT_KEY.to_string(), Value::from("hiking"))]),
+            ..Default::default()
+        };
+        let idea = Idea::from(point);
+        assert_eq!(idea.id, None);
+        assert_eq!(idea.content, "hiking");
+        assert_eq!(try_extract_u64_id(PointId::from(7)), Some(7));
     }
 }
@@ -1,11 +1,22 @@
-use embedding::{EmbeddingError, EmbeddingModel};
-use qdrant_client::Payload;
-use std::collections::HashMap;
+use embedding::{Distance, EmbeddingError, EmbeddingModel, ModelInfo};
+use qdrant_client::{Payload, qdrant};
+use std::collections::HashSet;
+use tracing::{Instrument, debug_span, instrument, warn};
 
-use crate::storage::{TOPIC_CONTENT_KEY, TOPIC_NAME_KEY};
+use crate::{
+    chunking::split_into_chunks,
+    idea::chunk_index,
+    storage::{
+        CHUNK_CONTENT_KEY, CHUNK_INDEX_KEY, PARENT_ID_KEY, TOPIC_CONTENT_KEY, TOPIC_NAME_KEY,
+    },
+};
 
-pub mod storage;
+pub use chunking::ChunkingConfig;
+
+pub mod chunking;
 pub mod idea;
+pub mod metrics;
+pub mod storage;
 
 #[derive(Debug, thiserror::Error)]
 pub enum TopicStorageError {
@@ -13,6 +24,16 @@ pub enum TopicStorageError {
     QdrantError(String),
     #[error("Embedding error: {0}")]
     EmbeddingError(EmbeddingError),
+    #[error("Cannot reindex collection '{0}' into itself")]
+    ReindexIntoItself(String),
+    #[error(
+        "Collection '{collection}' was built with {found}, but the current model is {expected}"
+    )]
+    ModelMismatch {
+        collection: String,
+        expected: ModelInfo,
+        found: ModelInfo,
+    },
 }
 
 type Result<T> = std::result::Result<T, TopicStorageError>;
@@ -21,6 +42,8 @@ pub struct Idea {
     pub topic_name: String,
     pub content: String,
     pub id: Option<u64>,
+    /// Best matching chunk of a chunked idea, set on search results
+    pub snippet: Option<String>,
 }
 
 impl Idea {
@@ -29,121 +52,539 @@ impl Idea {
             topic_name,
             content,
             id,
+            snippet: None,
         }
     }
 }
 
+/// Default collection name for a model.
+///
+/// Collections are per model so that switching providers never mixes
+/// embeddings of different models or dimensions in one collection.
+pub fn collection_name_for(model: &ModelInfo) -> String {
+    let slug: String = model
+        .id
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("topic_storage_{}_{}", slug, model.dimension)
+}
+
 pub struct TopicStorage<T: EmbeddingModel> {
     storage: storage::Storage,
     qdrant_collection_name: String,
     embedding_model: T,
+    chunking: Option<ChunkingConfig>,
 }
 
 impl<T: EmbeddingModel> TopicStorage<T> {
     pub async fn new(qdrant_endpoint: &str, embedding_model: T) -> Result<Self> {
         let storage = storage::Storage::new(qdrant_endpoint)
             .map_err(|e| TopicStorageError::QdrantError(e.to_string()))?;
-        let qdrant_collection_name = "topic_storage".to_string();
+        let qdrant_collection_name = collection_name_for(embedding_model.model_info());
 
         Ok(Self {
             storage,
             qdrant_collection_name,
             embedding_model,
+            chunking: None,
         })
     }
 
-    pub async fn create_topic(&self, topic_name: &str, idea: Idea) -> Result<()> {
-        let embedding = self
-            .embedding_model
-            .embed(&idea.content)
+    /// Use a custom collection instead of the one derived from the model.
+    pub fn with_collection_name(mut self, collection_name: impl Into<String>) -> Self {
+        self.qdrant_collection_name = collection_name.into();
+        self
+    }
+
+    /// Split long ideas into overlapping chunks, each embedded as its own point.
+    pub fn with_chunking(mut self, chunking: ChunkingConfig) -> Self {
+        self.chunking = Some(chunking);
+        self
+    }
+
+    /// How ideas are split before embedding, `None` if they aren't.
+    pub fn chunking(&self) -> Option<&ChunkingConfig> {
+        self.chunking.as_ref()
+    }
+
+    pub fn collection_name(&self) -> &str {
+        &self.qdrant_collection_name
+    }
+
+    /// Model new ideas and queries are embedded with.
+    pub fn model_info(&self) -> &ModelInfo {
+        self.embedding_model.model_info()
+    }
+
+    /// Check that Qdrant answers, returning its version.
+    pub async fn check_qdrant(&self) -> Result<String> {
+        self.storage
+            .health_check()
             .await
-            .map_err(|e| TopicStorageError::EmbeddingError(e))?;
+            // Keep the cause, which says why Qdrant is unreachable.
+            .map_err(|e| TopicStorageError::QdrantError(format!("{e:#}")))
+    }
+
+    /// Check that the embedding provider answers with embeddings of the
+    /// advertised dimension.
+    pub async fn check_embedding(&self) -> Result<()> {
+        self.embedding_model
+            .health_check()
+            .await
+            .map_err(TopicStorageError::EmbeddingError)
+    }
 
+    /// Number of points in the collection, or `None` if it doesn't exist yet.
+    ///
+    /// Chunked ideas count once per chunk.
+    pub async fn point_count(&self) -> Result<Option<u64>> {
+        let exists = self
+            .storage
+            .collection_exists(&self.qdrant_collection_name)
+            .await
+            .map_err(|e| TopicStorageError::QdrantError(e.to_string()))?;
+        if !exists {
+            return Ok(None);
+        }
+        let info = self
+            .storage
+            .get_collection_info(&self.qdrant_collection_name)
+            .await
+            .map_err(|e| TopicStorageError::QdrantError(e.to_string()))?;
+        Ok(info.and_then(|info| info.points_count))
+    }
+
+    /// Store an idea, creating the collection if needed, and return its id.
+    pub async fn create_topic(&self, topic_name: &str, idea: Idea) -> Result<u64> {
+        self.ensure_collection().await?;
+        self.update_topic(topic_name, idea).await
+    }
+
+    /// Create the collection for the current model if it does not exist yet.
+    ///
+    /// The vector is named after the model id, which together with its size and
+    /// distance records the model in the collection itself.
+    async fn ensure_collection(&self) -> Result<()> {
         if !self
             .storage
             .collection_exists(&self.qdrant_collection_name)
             .await
             .map_err(|e| TopicStorageError::QdrantError(e.to_string()))?
         {
+            let model = self.embedding_model.model_info();
             self.storage
-                .create_collection(&self.qdrant_collection_name, embedding.len() as u64)
+                .create_collection(
+                    &self.qdrant_collection_name,
+                    &model.id,
+                    model.dimension as u64,
+                    qdrant_distance(model.distance),
+                )
                 .await
                 .map_err(|e| TopicStorageError::QdrantError(e.to_string()))?;
         }
-        self.update_topic(topic_name, idea).await?;
         Ok(())
     }
 
-    pub async fn update_topic(&self, topic_name: &str, idea: Idea) -> Result<()> {
-        let embedding = self
-            .embedding_model
-            .embed(&idea.content)
-            .await
-            .map_err(|e| TopicStorageError::EmbeddingError(e))?;
+    /// Store an idea, replacing the one with the same id if it has one, and
+    /// return its id.
+    pub async fn update_topic(&self, topic_name: &str, idea: Idea) -> Result<u64> {
+        self.verify_collection_model().await?;
+        self.upsert_idea(topic_name, idea).await
+    }
 
-        let payload: Payload = HashMap::from([
-            (TOPIC_NAME_KEY.to_string(), topic_name.into()),
-            (TOPIC_CONTENT_KEY.to_string(), idea.content.into()),
-        ])
-        .try_into()
-        .map_err(|_| {
-            TopicStorageError::QdrantError(
-                "Failed to convert payload to Qdrant payload".to_string(),
-            )
-        })?;
+    async fn upsert_idea(&self, topic_name: &str, idea: Idea) -> Result<u64> {
+        let replaces_existing = idea.id.is_some();
+        let idea_id = idea.id.unwrap_or_else(new_idea_id);
+        let chunks = match &self.chunking {
+            Some(chunking) => split_into_chunks(&idea.content, chunking),
+            None => Vec::new(),
+        };
 
+        // Everything is embedded before anything is written, so a failure
+        // leaves the stored version of the idea untouched.
+        let points: Vec<_> = if chunks.len() <= 1 {
+            let embeddings = self.embed_documents(&[idea.content.as_str()]).await?;
+            let payload = Payload::from([
+                (TOPIC_NAME_KEY, topic_name.into()),
+                (TOPIC_CONTENT_KEY, idea.content.into()),
+            ]);
+            embeddings
+                .into_iter()
+                .map(|embedding| (idea_id, embedding, payload.clone()))
+                .collect()
+        } else {
+            let inputs: Vec<&str> = chunks.iter().map(String::as_str).collect();
+            let embeddings = self.embed_documents(&inputs).await?;
+            // The first chunk carries the idea id, so the idea can be addressed as
+            // a whole; the others get fresh ids and point back to it.
+            chunks
+                .into_iter()
+                .zip(embeddings)
+                .enumerate()
+                .map(|(index, (chunk, embedding))| {
+                    let point_id = if index == 0 { idea_id } else { new_idea_id() };
+                    let payload = Payload::from([
+                        (TOPIC_NAME_KEY, topic_name.into()),
+                        (TOPIC_CONTENT_KEY, idea.content.clone().into()),
+                        (PARENT_ID_KEY, (idea_id as i64).into()),
+                        (CHUNK_INDEX_KEY, (index as i64).into()),
+                        (CHUNK_CONTENT_KEY, chunk.into()),
+                    ]);
+                    (point_id, embedding, payload)
+                })
+                .collect()
+        };
+
+        let point_ids: Vec<u64> = points.iter().map(|(point_id, ..)| *point_id).collect();
         self.storage
-            .upsert_point(&self.qdrant_collection_name, embedding, payload)
+            .upsert_points(
+                &self.qdrant_collection_name,
+                &self.embedding_model.model_info().id,
+                points,
+            )
             .await
             .map_err(|e| TopicStorageError::QdrantError(e.to_string()))?;
+        if replaces_existing {
+            // Drop chunks of the previous version that the new one didn't
+            // overwrite, as it may have been split differently.
+            self.storage
+                .delete_points_by_parent(&self.qdrant_collection_name, idea_id, &point_ids)
+                .await
+                .map_err(|e| TopicStorageError::QdrantError(e.to_string()))?;
+        }
+        Ok(idea_id)
+    }
 
-        Ok(())
+    #[instrument(
+        level = "debug",
+        name = "embed",
+        skip_all,
+        fields(model = %self.embedding_model.model_info().id, kind = "document", inputs = inputs.len())
+    )]
+    async fn embed_documents(&self, inputs: &[&str]) -> Result<Vec<Vec<f32>>> {
+        let model = self.embedding_model.model_info();
+        let embeddings = metrics::embedding(
+            "document",
+            inputs,
+            self.embedding_model.embed_documents(inputs),
+        )
+        .await
+        .map_err(TopicStorageError::EmbeddingError)?;
+        if embeddings.len() != inputs.len() {
+            return Err(TopicStorageError::EmbeddingError(
+                EmbeddingError::RequestError(eyre::eyre!(
+                    "Expected {} embeddings, got {}",
+                    inputs.len(),
+                    embeddings.len()
+                )),
+            ));
+        }
+        if let Some(embedding) = embeddings.iter().find(|e| e.len() != model.dimension) {
+            return Err(TopicStorageError::ModelMismatch {
+                collection: self.qdrant_collection_name.clone(),
+                expected: model.clone(),
+                found: ModelInfo {
+                    dimension: embedding.len(),
+                    ..model.clone()
+                },
+            });
+        }
+        Ok(embeddings)
     }
+
+    /// Search ideas similar to `query`.
+    ///
+    /// Chunks of the same idea are collapsed into a single result, ranked by
+    /// its best-scoring chunk which is returned as the snippet.
     pub async fn search_topic(
         &self,
         topic: Option<&str>,
         query: &str,
         limit: u64,
     ) -> Result<Vec<Idea>> {
-        let embedding = self
-            .embedding_model
-            .embed(query)
-            .await
-            .map_err(|e| TopicStorageError::EmbeddingError(e))?;
+        let embedding =
+            metrics::embedding("query", &[query], self.embedding_model.embed_query(query))
+                .instrument(debug_span!(
+                    "embed",
+                    model = %self.embedding_model.model_info().id,
+                    kind = "query"
+                ))
+                .await
+                .map_err(TopicStorageError::EmbeddingError)?;
 
+        // Several hits may belong to the same idea, so ask for more than needed.
+        let search_limit = match self.chunking {
+            Some(_) => limit.saturating_mul(CHUNK_OVERSAMPLING),
+            None => limit,
+        };
+        let vector_name = &self.embedding_model.model_info().id;
         let results = if let Some(topic) = topic {
             self.storage
-                .get_points_by_topic(&self.qdrant_collection_name, topic, embedding)
+                .get_points_by_topic(
+                    &self.qdrant_collection_name,
+                    vector_name,
+                    topic,
+                    embedding,
+                    search_limit,
+                )
                 .await
                 .map_err(|e| TopicStorageError::QdrantError(e.to_string()))?
         } else {
             self.storage
-                .search_points(&self.qdrant_collection_name, embedding, limit)
+                .search_points(
+                    &self.qdrant_collection_name,
+                    vector_name,
+                    embedding,
+                    search_limit,
+                )
                 .await
                 .map_err(|e| TopicStorageError::QdrantError(e.to_string()))?
         };
+
+        let mut seen = HashSet::new();
         Ok(results
             .iter()
-            .map(|r| r.into())
+            .map(Idea::from)
+            .filter(|idea| idea.id.is_none_or(|id| seen.insert(id)))
+            .take(limit as usize)
             .collect())
     }
-    pub async fn list_topic(&self, topic: &str, limit: u32) -> Result<Vec<String>> {
+    pub async fn list_topic(&self, topic: &str, limit: u32) -> Result<Vec<Idea>> {
         let results = self
             .storage
             .list_points_by_topic(&self.qdrant_collection_name, topic, limit)
             .await
             .map_err(|e| TopicStorageError::QdrantError(e.to_string()))?;
+        // Only the first chunk of an idea is listed; its text is no snippet.
         Ok(results
             .iter()
-            .map(|r| r.payload.get(TOPIC_CONTENT_KEY).unwrap().to_string())
+            .map(|point| Idea {
+                snippet: None,
+                ..Idea::from(point)
+            })
             .collect())
     }
 
+    /// Model the collection was built with, if the collection exists.
+    pub async fn collection_model(&self) -> Result<Option<ModelInfo>> {
+        let params = self
+            .storage
+            .get_vector_params(&self.qdrant_collection_name)
+            .await
+            .map_err(|e| TopicStorageError::QdrantError(e.to_string()))?;
+        Ok(params.map(|(vector_name, params)| ModelInfo {
+            id: vector_name,
+            dimension: params.size as usize,
+            distance: embedding_distance(params.distance()),
+        }))
+    }
+
+    /// Refuse to touch a collection that was built with a different model.
+    async fn verify_collection_model(&self) -> Result<()> {
+        let expected = self.embedding_model.model_info();
+        match self.collection_model().await? {
+            Some(found) if &found != expected => Err(TopicStorageError::ModelMismatch {
+                collection: self.qdrant_collection_name.clone(),
+                expected: expected.clone(),
+                found,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Copy every idea of `source_collection` into this storage's collection,
+    /// re-embedding it with the current model.
+    ///
+    /// Idea ids are preserved, so running it again overwrites instead of
+    /// duplicating; points with UUIDs are skipped, as their ids can't be.
+    /// Returns the number of reindexed ideas.
+    pub async fn reindex_from(&self, source_collection: &str) -> Result<u64> {
+        if source_collection == self.qdrant_collection_name {
+            return Err(TopicStorageError::ReindexIntoItself(
+                source_collection.to_string(),
+            ));
+        }
+        self.ensure_collection().await?;
+        self.verify_collection_model().await?;
+
+        let mut reindexed = 0;
+        let mut offset = None;
+        loop {
+            let (points, next_offset) = self
+                .storage
+                .scroll_points(source_collection, offset, REINDEX_BATCH_SIZE)
+                .await
+                .map_err(|e| TopicStorageError::QdrantError(e.to_string()))?;
+            for point in points {
+                // Chunked ideas are re-split from the full content of their first chunk.
+                if chunk_index(&point.payload).is_some_and(|index| index > 0) {
+                    continue;
+                }
+                let idea = Idea::from(point);
+                // A fresh id would duplicate the idea each time it is reindexed.
+                if idea.id.is_none() {
+                    warn!(
+                        collection = source_collection,
+                        "Skipping a point without a numeric id"
+                    );
+                    continue;
+                }
+                let topic_name = idea.topic_name.clone();
+                self.upsert_idea(&topic_name, idea).await?;
+                reindexed += 1;
+            }
+            match next_offset {
+                Some(next_offset) => offset = Some(next_offset),
+                None => break,
+            }
+        }
+        Ok(reindexed)
+    }
+
     pub async fn delete_idea(&self, idea_id: u64) -> Result<()> {
         self.storage
             .delete_point(&self.qdrant_collection_name, idea_id)
             .await
             .map_err(|e| TopicStorageError::QdrantError(e.to_string()))?;
+        self.storage
+            .delete_points_by_parent(&self.qdrant_collection_name, idea_id, &[])
+            .await
+            .map_err(|e| TopicStorageError::QdrantError(e.to_string()))?;
         Ok(())
     }
 }
+
+const REINDEX_BATCH_SIZE: u32 = 64;
+const CHUNK_OVERSAMPLING: u64 = 4;
+
+fn new_idea_id() -> u64 {
+    uuid::Uuid::new_v4().as_u64_pair().0
+}
+
+fn qdrant_distance(distance: Distance) -> qdrant::Distance {
+    match distance {
+        Distance::Cosine => qdrant::Distance::Cosine,
+        Distance::Dot => qdrant::Distance::Dot,
+        Distance::Euclid => qdrant::Distance::Euclid,
+        Distance::Manhattan => qdrant::Distance::Manhattan,
+    }
+}
+
+fn embedding_distance(distance: qdrant::Distance) -> Distance {
+    match distance {
+        qdrant::Distance::Dot => Distance::Dot,
+        qdrant::Distance::Euclid => Distance::Euclid,
+        qdrant::Distance::Manhattan => Distance::Manhattan,
+        qdrant::Distance::Cosine | qdrant::Distance::UnknownDistance => Distance::Cosine,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedding::hashing::HashingEmbedder;
+    use uuid::Uuid;
+
+    #[tokio::test]
+    async fn test_create_and_search_topic() {
+        let collection_name = format!("test_collection_{}", Uuid::new_v4());
+        let storage = TopicStorage::new("http://localhost:6334", HashingEmbedder::new(64))
+            .await
+            .unwrap()
+            .with_collection_name(&collection_name);
+
+        let mut ids = Vec::new();
+        for content in ["hiking in the mountains", "baking sourdough bread"] {
+            let id = storage
+                .create_topic(
+                    "weekend",
+                    Idea::new("weekend".to_string(), content.to_string(), None),
+                )
+                .await
+                .unwrap();
+            ids.push(id);
+        }
+
+        let results = storage
+            .search_topic(None, "mountain hike", 1)
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].content, "hiking in the mountains");
+        assert_eq!(results[0].topic_name, "weekend");
+        assert_eq!(results[0].id, Some(ids[0]));
+
+        let listed = storage.list_topic("weekend", 10).await.unwrap();
+        assert_eq!(listed.len(), 2);
+        assert!(
+            listed
+                .iter()
+                .all(|idea| idea.id.is_some_and(|id| ids.contains(&id)))
+        );
+
+        storage
+            .storage
+            .delete_collection(&collection_name)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_update_replaces_chunks() {
+        let collection_name = format!("test_collection_{}", Uuid::new_v4());
+        let storage = TopicStorage::new("http://localhost:6334", HashingEmbedder::new(64))
+            .await
+            .unwrap()
+            .with_collection_name(&collection_name)
+            .with_chunking(ChunkingConfig {
+                max_words: 4,
+                overlap_words: 0,
+            });
+
+        let long = "walk along the river and then climb the hill for the view";
+        let id = storage
+            .create_topic(
+                "weekend",
+                Idea::new("weekend".to_string(), long.to_string(), None),
+            )
+            .await
+            .unwrap();
+        let short = "climb the hill";
+        let updated = storage
+            .update_topic(
+                "weekend",
+                Idea::new("weekend".to_string(), short.to_string(), Some(id)),
+            )
+            .await
+            .unwrap();
+        assert_eq!(updated, id);
+
+        // The single point of the new version replaced all chunks of the old one.
+        assert_eq!(storage.point_count().await.unwrap(), Some(1));
+        let listed = storage.list_topic("weekend", 10).await.unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].content, short);
+
+        storage
+            .storage
+            .delete_collection(&collection_name)
+            .await
+            .unwrap();
+    }
+
+    #[test]
+    fn test_collection_name_for() {
+        let model = ModelInfo::new("BAAI/bge-base-en-v1.5", 768);
+        assert_eq!(
+            collection_name_for(&model),
+            "topic_storage_baai_bge_base_en_v1_5_768"
+        );
+    }
+}
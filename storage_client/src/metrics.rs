@@ -0,0 +1,84 @@
+//! Metrics of embedding calls and Qdrant operations.
+//!
+//! They are recorded through the `metrics` facade and go nowhere unless the
+//! binary installs a recorder, such as the daemon's Prometheus exporter.
+//!
+//! Providers don't report the tokens they bill, so [`EMBEDDING_WORDS`] counts
+//! the words sent instead. There are no cache hits to count: embeddings and
+//! Qdrant results aren't cached.
+
+use metrics::{Unit, counter, describe_counter, describe_histogram, histogram};
+use std::{future::Future, time::Instant};
+
+/// Duration of embedding calls, labeled by `kind` (`document` or `query`).
+pub const EMBEDDING_DURATION: &str = "qdrant_cli_embedding_duration_seconds";
+/// Failed embedding calls, labeled by `kind`.
+pub const EMBEDDING_ERRORS: &str = "qdrant_cli_embedding_errors_total";
+/// Words sent to the embedding provider, labeled by `kind`.
+///
+/// Providers don't report the tokens they bill, so words stand in for them,
+/// as they do for chunking.
+pub const EMBEDDING_WORDS: &str = "qdrant_cli_embedding_words_total";
+/// Duration of Qdrant calls, labeled by `operation`.
+pub const QDRANT_DURATION: &str = "qdrant_cli_qdrant_duration_seconds";
+/// Failed Qdrant calls, labeled by `operation`.
+pub const QDRANT_ERRORS: &str = "qdrant_cli_qdrant_errors_total";
+
+/// Register the descriptions of the metrics above with the installed recorder.
+pub fn describe() {
+    describe_histogram!(
+        EMBEDDING_DURATION,
+        Unit::Seconds,
+        "Duration of embedding calls"
+    );
+    describe_counter!(EMBEDDING_ERRORS, "Failed embedding calls");
+    describe_counter!(
+        EMBEDDING_WORDS,
+        "Words sent to the embedding provider, approximating tokens"
+    );
+    describe_histogram!(QDRANT_DURATION, Unit::Seconds, "Duration of Qdrant calls");
+    describe_counter!(QDRANT_ERRORS, "Failed Qdrant calls");
+}
+
+/// Await an embedding call of `inputs`.
+pub(crate) async fn embedding<T, E>(
+    kind: &'static str,
+    inputs: &[&str],
+    call: impl Future<Output = Result<T, E>>,
+) -> Result<T, E> {
+    let words = inputs
+        .iter()
+        .map(|input| input.split_whitespace().count())
+        .sum::<usize>();
+    counter!(EMBEDDING_WORDS, "kind" => kind).increment(words as u64);
+    timed(EMBEDDING_DURATION, EMBEDDING_ERRORS, ("kind", kind), call).await
+}
+
+/// Await the Qdrant call `operation`.
+pub(crate) async fn qdrant<T, E>(
+    operation: &'static str,
+    call: impl Future<Output = Result<T, E>>,
+) -> Result<T, E> {
+    timed(
+        QDRANT_DURATION,
+        QDRANT_ERRORS,
+        ("operation", operation),
+        call,
+    )
+    .await
+}
+
+async fn timed<T, E>(
+    duration: &'static str,
+    errors: &'static str,
+    label: (&'static str, &'static str),
+    call: impl Future<Output = Result<T, E>>,
+) -> Result<T, E> {
+    let started = Instant::now();
+    let result = call.await;
+    histogram!(duration, label.0 => label.1).record(started.elapsed());
+    if result.is_err() {
+        counter!(errors, label.0 => label.1).increment(1);
+    }
+    result
+}
@@ -3,162 +3,320 @@ use qdrant_client::{
     Payload, Qdrant,
     qdrant::{
         CollectionInfo, Condition, CreateCollectionBuilder, DeletePointsBuilder, Distance, Filter,
-        GetCollectionInfoRequest, GetCollectionInfoResponse, PointStruct, PointsIdsList,
-        QueryPointsBuilder, RetrievedPoint, ScalarQuantizationBuilder, ScoredPoint,
-        ScrollPointsBuilder, SearchPointsBuilder, UpsertPointsBuilder, VectorParamsBuilder,
+        NamedVectors, PointId, PointStruct, PointsIdsList, QueryPointsBuilder, Range,
+        RetrievedPoint, ScalarQuantizationBuilder, ScoredPoint, ScrollPointsBuilder,
+        SearchPointsBuilder, UpsertPointsBuilder, VectorParams, VectorParamsBuilder,
+        VectorsConfigBuilder, vectors_config::Config,
     },
 };
+use tracing::instrument;
+
+use crate::metrics;
 
 pub(crate) struct Storage {
-    endpoint: String,
     client: Qdrant,
 }
 
 pub(super) const TOPIC_NAME_KEY: &str = "topic_name";
 pub(super) const TOPIC_CONTENT_KEY: &str = "topic_content";
+/// Id of the idea a chunk belongs to; only set on chunked ideas.
+pub(super) const PARENT_ID_KEY: &str = "parent_id";
+pub(super) const CHUNK_INDEX_KEY: &str = "chunk_index";
+pub(super) const CHUNK_CONTENT_KEY: &str = "chunk_content";
+
+/// Matches every chunk of a chunked idea except the first one, which carries
+/// the idea id and stands for the idea when listing.
+fn secondary_chunk() -> Condition {
+    Condition::range(
+        CHUNK_INDEX_KEY,
+        Range {
+            gt: Some(0.0),
+            ..Default::default()
+        },
+    )
+}
 
 impl Storage {
     pub(crate) fn new(endpoint: &str) -> Result<Self> {
-        let client = Qdrant::from_url(&endpoint)
+        let client = Qdrant::from_url(endpoint)
             .build()
             .wrap_err("Failed to create Qdrant client")?;
-        Ok(Self {
-            endpoint: endpoint.to_string(),
-            client,
-        })
+        Ok(Self { client })
     }
 
+    /// Create a collection holding a single named vector.
+    ///
+    /// The vector is named after the embedding model so the collection records
+    /// which model its points were embedded with.
+    #[instrument(level = "debug", skip_all, fields(collection = collection_name))]
     pub(crate) async fn create_collection(
         &self,
         collection_name: &str,
+        vector_name: &str,
         vector_size: u64,
+        distance: Distance,
     ) -> Result<()> {
+        let mut vectors_config = VectorsConfigBuilder::default();
+        vectors_config
+            .add_named_vector_params(vector_name, VectorParamsBuilder::new(vector_size, distance));
         let create_collection = CreateCollectionBuilder::new(collection_name)
-            .vectors_config(VectorParamsBuilder::new(vector_size, Distance::Cosine))
+            .vectors_config(vectors_config)
             .quantization_config(ScalarQuantizationBuilder::default());
 
-        self.client
-            .create_collection(create_collection)
-            .await
-            .wrap_err("Failed to create collection")?;
+        metrics::qdrant(
+            "create_collection",
+            self.client.create_collection(create_collection),
+        )
+        .await
+        .wrap_err("Failed to create collection")?;
 
         Ok(())
     }
 
+    #[cfg(test)]
     pub(crate) async fn upsert_point(
         &self,
         collection_name: &str,
+        point_id: u64,
+        vector_name: &str,
         point: Vec<f32>,
         payload: Payload,
     ) -> Result<()> {
-        let point = PointStruct::new(0, point, payload);
-        self.client
-            .upsert_points(UpsertPointsBuilder::new(collection_name, vec![point]))
-            .await
-            .wrap_err("Failed to upsert point")?;
+        self.upsert_points(
+            collection_name,
+            vector_name,
+            vec![(point_id, point, payload)],
+        )
+        .await
+    }
+
+    /// Write several points in a single request.
+    #[instrument(level = "debug", skip_all, fields(collection = collection_name, points = points.len()))]
+    pub(crate) async fn upsert_points(
+        &self,
+        collection_name: &str,
+        vector_name: &str,
+        points: Vec<(u64, Vec<f32>, Payload)>,
+    ) -> Result<()> {
+        let points = points
+            .into_iter()
+            .map(|(point_id, point, payload)| {
+                PointStruct::new(
+                    point_id,
+                    NamedVectors::default().add_vector(vector_name, point),
+                    payload,
+                )
+            })
+            .collect::<Vec<_>>();
+        metrics::qdrant(
+            "upsert_points",
+            self.client
+                .upsert_points(UpsertPointsBuilder::new(collection_name, points)),
+        )
+        .await
+        .wrap_err("Failed to upsert points")?;
 
         Ok(())
     }
+    #[instrument(level = "debug", skip_all, fields(collection = collection_name, topic = topic_name))]
     pub(crate) async fn list_points_by_topic(
         &self,
         collection_name: &str,
         topic_name: &str,
         limit: u32,
     ) -> Result<Vec<RetrievedPoint>> {
-        let response = self
-            .client
-            .scroll(
+        let response = metrics::qdrant(
+            "scroll",
+            self.client.scroll(
                 ScrollPointsBuilder::new(collection_name)
-                    .filter(Filter::must([Condition::matches(
-                        TOPIC_NAME_KEY,
-                        topic_name.to_string(),
-                    )]))
+                    .filter(Filter {
+                        must: vec![Condition::matches(TOPIC_NAME_KEY, topic_name.to_string())],
+                        must_not: vec![secondary_chunk()],
+                        ..Default::default()
+                    })
                     .limit(limit)
                     .with_vectors(false)
                     .with_payload(true),
-            )
+            ),
+        )
+        .await
+        .wrap_err("Failed to scroll points")?;
+        Ok(response.result)
+    }
+    /// Scroll through every point of a collection, one page at a time.
+    ///
+    /// Returns the page and the offset of the next one, if any.
+    #[instrument(level = "debug", skip_all, fields(collection = collection_name))]
+    pub(crate) async fn scroll_points(
+        &self,
+        collection_name: &str,
+        offset: Option<PointId>,
+        limit: u32,
+    ) -> Result<(Vec<RetrievedPoint>, Option<PointId>)> {
+        let mut scroll = ScrollPointsBuilder::new(collection_name)
+            .limit(limit)
+            .with_vectors(false)
+            .with_payload(true);
+        if let Some(offset) = offset {
+            scroll = scroll.offset(offset);
+        }
+        let response = metrics::qdrant("scroll", self.client.scroll(scroll))
             .await
             .wrap_err("Failed to scroll points")?;
-        Ok(response.result)
+        Ok((response.result, response.next_page_offset))
     }
+    #[instrument(level = "debug", skip_all, fields(collection = collection_name, topic = topic_name, limit))]
     pub(crate) async fn get_points_by_topic(
         &self,
         collection_name: &str,
+        vector_name: &str,
         topic_name: &str,
         query: Vec<f32>,
+        limit: u64,
     ) -> Result<Vec<ScoredPoint>> {
-        let response = self
-            .client
-            .query(
+        let response = metrics::qdrant(
+            "query",
+            self.client.query(
                 QueryPointsBuilder::new(collection_name)
                     .query(query)
+                    .using(vector_name)
                     .filter(Filter::must([Condition::matches(
                         TOPIC_NAME_KEY,
                         topic_name.to_string(),
                     )]))
+                    .limit(limit)
                     .with_vectors(false)
                     .with_payload(true),
-            )
-            .await
-            .wrap_err("Failed to query points")?;
+            ),
+        )
+        .await
+        .wrap_err("Failed to query points")?;
         Ok(response.result)
     }
 
+    #[instrument(level = "debug", skip_all, fields(collection = collection_name, limit))]
     pub(crate) async fn search_points(
         &self,
         collection_name: &str,
+        vector_name: &str,
         query: Vec<f32>,
         limit: u64,
     ) -> Result<Vec<ScoredPoint>> {
-        let response = self
-            .client
-            .search_points(
+        let response = metrics::qdrant(
+            "search_points",
+            self.client.search_points(
                 SearchPointsBuilder::new(collection_name, query, limit)
+                    .vector_name(vector_name)
                     .with_vectors(false)
                     .with_payload(true),
-            )
-            .await
-            .wrap_err("Failed to search points")?;
+            ),
+        )
+        .await
+        .wrap_err("Failed to search points")?;
         Ok(response.result)
     }
+    #[instrument(level = "debug", skip_all, fields(collection = collection_name, point_id))]
     pub(crate) async fn delete_point(&self, collection_name: &str, point_id: u64) -> Result<()> {
-        self.client
-            .delete_points(
-                DeletePointsBuilder::new(collection_name).points(PointsIdsList {
-                    ids: vec![point_id.into()],
-                }),
-            )
-            .await
-            .wrap_err("Failed to delete point")?;
+        metrics::qdrant(
+            "delete_points",
+            self.client
+                .delete_points(
+                    DeletePointsBuilder::new(collection_name).points(PointsIdsList {
+                        ids: vec![point_id.into()],
+                    }),
+                ),
+        )
+        .await
+        .wrap_err("Failed to delete point")?;
         Ok(())
     }
+    /// Delete the chunks stored for a chunked idea, except the points in `keep`.
+    #[instrument(level = "debug", skip_all, fields(collection = collection_name, parent_id))]
+    pub(crate) async fn delete_points_by_parent(
+        &self,
+        collection_name: &str,
+        parent_id: u64,
+        keep: &[u64],
+    ) -> Result<()> {
+        let mut filter = Filter::must([Condition::matches(PARENT_ID_KEY, parent_id as i64)]);
+        if !keep.is_empty() {
+            filter.must_not = vec![Condition::has_id(keep.iter().copied())];
+        }
+        metrics::qdrant(
+            "delete_points",
+            self.client
+                .delete_points(DeletePointsBuilder::new(collection_name).points(filter)),
+        )
+        .await
+        .wrap_err("Failed to delete chunks")?;
+        Ok(())
+    }
+    #[instrument(level = "debug", skip_all, fields(collection = collection_name))]
     pub(crate) async fn get_collection_info(
         &self,
         collection_name: &str,
     ) -> Result<Option<CollectionInfo>> {
-        Ok(self
-            .client
-            .collection_info(collection_name)
-            .await
-            .wrap_err("Failed to retrieve collection info")?
-            .result)
+        Ok(metrics::qdrant(
+            "collection_info",
+            self.client.collection_info(collection_name),
+        )
+        .await
+        .wrap_err("Failed to retrieve collection info")?
+        .result)
     }
 
+    /// Name and parameters of the vector stored in a collection, if it exists.
+    pub(crate) async fn get_vector_params(
+        &self,
+        collection_name: &str,
+    ) -> Result<Option<(String, VectorParams)>> {
+        if !self.collection_exists(collection_name).await? {
+            return Ok(None);
+        }
+        let config = self
+            .get_collection_info(collection_name)
+            .await?
+            .and_then(|info| info.config)
+            .and_then(|config| config.params)
+            .and_then(|params| params.vectors_config)
+            .and_then(|vectors_config| vectors_config.config);
+
+        Ok(match config {
+            Some(Config::Params(params)) => Some((String::new(), params)),
+            Some(Config::ParamsMap(params_map)) => params_map.map.into_iter().next(),
+            None => None,
+        })
+    }
+
+    #[cfg(test)]
     pub(crate) async fn delete_collection(&self, collection_name: &str) -> Result<()> {
-        self.client
-            .delete_collection(collection_name)
-            .await
-            .wrap_err("Failed to delete collection")?;
+        metrics::qdrant(
+            "delete_collection",
+            self.client.delete_collection(collection_name),
+        )
+        .await
+        .wrap_err("Failed to delete collection")?;
 
         Ok(())
     }
 
-    pub(crate) async fn collection_exists(&self, collection_name: &str) -> Result<bool> {
-        let response = self
-            .client
-            .collection_exists(collection_name)
+    /// Version of the Qdrant server, which fails if it can't be reached.
+    #[instrument(level = "debug", skip_all)]
+    pub(crate) async fn health_check(&self) -> Result<String> {
+        let reply = metrics::qdrant("health_check", self.client.health_check())
             .await
-            .wrap_err("Failed to check if collection exists")?;
+            .wrap_err("Failed to reach Qdrant")?;
+        Ok(reply.version)
+    }
+
+    #[instrument(level = "debug", skip_all, fields(collection = collection_name))]
+    pub(crate) async fn collection_exists(&self, collection_name: &str) -> Result<bool> {
+        let response = metrics::qdrant(
+            "collection_exists",
+            self.client.collection_exists(collection_name),
+        )
+        .await
+        .wrap_err("Failed to check if collection exists")?;
         Ok(response)
     }
 }
@@ -166,22 +324,32 @@ impl Storage {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::collections::HashMap;
     use uuid::Uuid;
 
+    const TEST_VECTOR: &str = "test_model";
+
     #[tokio::test]
     async fn test_create_collection() {
         let storage = Storage::new("http://localhost:6334").unwrap();
         let collection_name = format!("test_collection_{}", Uuid::new_v4());
 
         storage
-            .create_collection(&collection_name, 3)
+            .create_collection(&collection_name, TEST_VECTOR, 3, Distance::Cosine)
             .await
             .unwrap();
 
         let collection_info = storage.get_collection_info(&collection_name).await.unwrap();
         assert_eq!(collection_info.unwrap().points_count, Some(0));
 
+        let (vector_name, params) = storage
+            .get_vector_params(&collection_name)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(vector_name, TEST_VECTOR);
+        assert_eq!(params.size, 3);
+        assert_eq!(params.distance, Distance::Cosine as i32);
+
         storage.delete_collection(&collection_name).await.unwrap();
     }
 
@@ -191,18 +359,17 @@ mod tests {
         let collection_name = format!("test_collection_{}", Uuid::new_v4());
 
         storage
-            .create_collection(&collection_name, 3)
+            .create_collection(&collection_name, TEST_VECTOR, 3, Distance::Cosine)
             .await
             .unwrap();
 
-        assert_eq!(
-            storage.collection_exists(&collection_name).await.unwrap(),
-            true
-        );
+        assert!(storage.collection_exists(&collection_name).await.unwrap());
 
         storage
             .upsert_point(
                 &collection_name,
+                1,
+                TEST_VECTOR,
                 vec![1.0, 2.0, 3.0],
                 serde_json::json!({
                     "key": "value"
@@ -224,12 +391,18 @@ mod tests {
         let collection_name = format!("test_collection_{}", Uuid::new_v4());
 
         storage
-            .create_collection(&collection_name, 3)
+            .create_collection(&collection_name, TEST_VECTOR, 3, Distance::Cosine)
             .await
             .unwrap();
 
         let point = storage
-            .get_points_by_topic(&collection_name, "test_topic", vec![1.0, 2.0, 3.0])
+            .get_points_by_topic(
+                &collection_name,
+                TEST_VECTOR,
+                "test_topic",
+                vec![1.0, 2.0, 3.0],
+                10,
+            )
             .await
             .unwrap();
         assert_eq!(point.len(), 0);
@@ -237,6 +410,8 @@ mod tests {
         storage
             .upsert_point(
                 &collection_name,
+                1,
+                TEST_VECTOR,
                 vec![1.0, 2.0, 3.0],
                 serde_json::json!({
                     TOPIC_NAME_KEY: "test_topic"
@@ -247,7 +422,13 @@ mod tests {
             .await
             .unwrap();
         let point = storage
-            .get_points_by_topic(&collection_name, "test_topic", vec![1.0, 2.0, 3.0])
+            .get_points_by_topic(
+                &collection_name,
+                TEST_VECTOR,
+                "test_topic",
+                vec![1.0, 2.0, 3.0],
+                10,
+            )
             .await
             .unwrap();
         assert_eq!(point.len(), 1);
@@ -260,12 +441,14 @@ mod tests {
         let collection_name = format!("test_collection_{}", Uuid::new_v4());
 
         storage
-            .create_collection(&collection_name, 3)
+            .create_collection(&collection_name, TEST_VECTOR, 3, Distance::Cosine)
             .await
             .unwrap();
         storage
             .upsert_point(
                 &collection_name,
+                1,
+                TEST_VECTOR,
                 vec![1.0, 2.0, 3.0],
                 serde_json::json!({
                     TOPIC_NAME_KEY: "test_topic"
@@ -281,6 +464,13 @@ mod tests {
             .unwrap();
         assert_eq!(points.len(), 1);
 
+        let (points, next_offset) = storage
+            .scroll_points(&collection_name, None, 10)
+            .await
+            .unwrap();
+        assert_eq!(points.len(), 1);
+        assert!(next_offset.is_none());
+
         storage.delete_collection(&collection_name).await.unwrap();
     }
 }